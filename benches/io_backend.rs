@@ -0,0 +1,129 @@
+//! Compares the I/O backends on the fill, fsync, rename and truncate steps of a wipe.
+//!
+//! ```sh
+//! cargo bench --bench io_backend --features io-uring
+//! ```
+//!
+//! Files are created in the system temporary directory, which is often a tmpfs;
+//! set `RUTABAGA_BENCH_DIR` to a directory on the disk under test.
+
+#[path = "../src/gui/cleaner/io.rs"]
+#[allow(dead_code, unused_imports)]
+mod io;
+
+use io::{Fill, IoBackend, TokioBackend};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+const CONCURRENCY: usize = 64;
+
+struct Workload {
+    name: &'static str,
+    files: usize,
+    size: usize,
+}
+
+const WORKLOADS: [Workload; 2] = [
+    Workload {
+        name: "20000 x 4 KiB",
+        files: 20_000,
+        size: 4 * 1024,
+    },
+    Workload {
+        name: "4 x 256 MiB",
+        files: 4,
+        size: 256 * 1024 * 1024,
+    },
+];
+
+#[allow(unused_mut)]
+fn backends() -> Vec<Arc<dyn IoBackend>> {
+    let mut backends: Vec<Arc<dyn IoBackend>> = vec![Arc::new(TokioBackend)];
+
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    match io::UringBackend::new() {
+        Ok(backend) => backends.push(Arc::new(backend)),
+        Err(err) => eprintln!("skipping io_uring: {err}"),
+    }
+
+    backends
+}
+
+#[tokio::main]
+async fn main() {
+    let base = std::env::var_os("RUTABAGA_BENCH_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rutabaga-bench");
+
+    println!(
+        "{:<16} {:<10} {:>10} {:>12}",
+        "workload", "backend", "time", "files/s"
+    );
+
+    for workload in &WORKLOADS {
+        for backend in backends() {
+            let files = prepare(&base, workload);
+            let elapsed = wipe(backend.clone(), files).await;
+            let rate = workload.files as f64 / elapsed.as_secs_f64();
+
+            println!(
+                "{:<16} {:<10} {:>9.2?} {:>12.0}",
+                workload.name,
+                backend.name(),
+                elapsed,
+                rate
+            );
+        }
+    }
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+fn prepare(base: &Path, workload: &Workload) -> Vec<PathBuf> {
+    let _ = std::fs::remove_dir_all(base);
+    std::fs::create_dir_all(base).expect("creating the benchmark directory");
+
+    let content = vec![0xA5; workload.size];
+    (0..workload.files)
+        .map(|i| {
+            let path = base.join(format!("file{i}.dat"));
+            std::fs::write(&path, &content).expect("creating a benchmark file");
+            path
+        })
+        .collect()
+}
+
+/// Wipes every file the way the `overwrite,rename,truncate` pipeline does.
+async fn wipe(backend: Arc<dyn IoBackend>, files: Vec<PathBuf>) -> Duration {
+    let permits = Arc::new(Semaphore::new(CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    let start = Instant::now();
+
+    for (index, path) in files.into_iter().enumerate() {
+        let permit = permits.clone().acquire_owned().await.unwrap();
+        let backend = backend.clone();
+
+        tasks.spawn(async move {
+            let len = tokio::fs::metadata(&path).await?.len();
+            backend.fill(&path, 0, len, Fill::Zeros, &|_| {}).await?;
+            backend.fsync(&path).await?;
+
+            let renamed = path.with_file_name(format!("File{index}.txt"));
+            backend.rename(&path, &renamed).await?;
+            backend.write(&renamed, Vec::new()).await?;
+
+            drop(permit);
+            std::io::Result::Ok(())
+        });
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        result.unwrap().expect("wiping a benchmark file");
+    }
+
+    start.elapsed()
+}
@@ -0,0 +1,837 @@
+use crate::gui::cleaner::action::{
+    Delete, Hash, Naming, Overwrite, Pipeline, Placeholder, Rename, Replace, StripMetadata,
+    Timestamps, Truncate,
+};
+use crate::gui::cleaner::busy::BusyCheck;
+use crate::gui::cleaner::compress::{Codec, Compress};
+use crate::gui::cleaner::dirs::{DirectoryRenamer, DirectoryTimes};
+use crate::gui::cleaner::discover::Discover;
+use crate::gui::cleaner::document::StripDocumentMetadata;
+use crate::gui::cleaner::image::StripImageMetadata;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+use crate::gui::cleaner::io::UringBackend;
+use crate::gui::cleaner::io::{IoBackend, TokioBackend};
+use crate::gui::cleaner::limit::Limits;
+use crate::gui::cleaner::media::StripMediaTags;
+use crate::gui::cleaner::organize::{Organize, SortRule};
+use crate::gui::cleaner::pattern::{Case, RenamePattern};
+use crate::gui::cleaner::pseudonym::Pseudonyms;
+use crate::gui::cleaner::redact::{Detector, Redact};
+use crate::gui::cleaner::sanitize::Sanitize;
+use crate::gui::cleaner::source::{
+    DirectorySource, FileSource, GitUntrackedSource, GlobSource, ListSource, ScanSource,
+};
+use crate::gui::cleaner::tail::{KeepTail, Tail};
+use clap::error::ErrorKind;
+use clap::{ArgGroup, CommandFactory, Parser, ValueEnum};
+use itertools::Itertools;
+use regex::Regex;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// File cleaner app
+#[derive(Debug, Clone, Default, Parser)]
+#[command(version, about)]
+#[command(group(ArgGroup::new("source").multiple(false)))]
+#[command(group(ArgGroup::new("placeholders").multiple(false)))]
+#[command(group(ArgGroup::new("tail").multiple(false)))]
+pub struct Args {
+    /// Comma-separated actions applied to every file, replacing the operation selected in the window
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub actions: Vec<ActionKind>,
+
+    /// Longest name in bytes the `sanitize` action leaves, extension included
+    #[arg(long, value_name = "BYTES", default_value_t = 255, value_parser = clap::value_parser!(u16).range(16..))]
+    pub max_name_bytes: u16,
+
+    /// Character the `sanitize` action puts in place of forbidden ones
+    #[arg(long, value_name = "CHAR", default_value_t = '_', value_parser = parse_replacement)]
+    pub replacement: char,
+
+    /// What the `organize` action sorts files into subfolders by
+    #[arg(long, value_enum, value_name = "RULE", default_value_t = OrganizeBy::Extension)]
+    pub organize_by: OrganizeBy,
+
+    /// Record where the `organize` action would move every file to the --report instead of moving it, skipping the actions after it
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Text the `replace` action puts in place of the content [default: REDACTED]
+    #[arg(long, value_name = "TEXT", group = "placeholders")]
+    pub placeholder: Option<String>,
+
+    /// File whose content the `replace` action puts in place of the content
+    #[arg(long, value_name = "FILE", group = "placeholders")]
+    pub placeholder_file: Option<PathBuf>,
+
+    /// Make the `replace` action write random bytes, keeping the size of the files
+    #[arg(long, group = "placeholders")]
+    pub random_placeholder: bool,
+
+    /// Number of lines at the end of the files the `keep-tail` action keeps [default: 1000]
+    #[arg(long, value_name = "N", group = "tail")]
+    pub tail_lines: Option<u64>,
+
+    /// Number of bytes at the end of the files the `keep-tail` action keeps, with an optional K, M or G suffix
+    #[arg(long, value_name = "SIZE", group = "tail", value_parser = parse_size)]
+    pub tail_bytes: Option<u64>,
+
+    /// Compress what the `keep-tail` action cuts to {name}.head.gz next to the file
+    #[arg(long)]
+    pub archive_head: bool,
+
+    /// Format the `compress` action writes
+    #[arg(long, value_enum, value_name = "CODEC", default_value_t = Compression::Zstd)]
+    pub compression: Compression,
+
+    /// Compression level of the `compress` action, 0-9 for gzip and xz, 1-22 for zstd [default: 6 for gzip and xz, 3 for zstd]
+    #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(u32).range(0..=22))]
+    pub level: Option<u32>,
+
+    /// Built-in detectors of the `redact` action, comma-separated [default: all]
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        conflicts_with = "no_detectors"
+    )]
+    pub detectors: Vec<DetectorKind>,
+
+    /// Make the `redact` action only look for --redact-pattern matches
+    #[arg(long, requires = "redact_pattern")]
+    pub no_detectors: bool,
+
+    /// Regular expression whose matches the `redact` action masks, only its group named `secret` if it has one. Repeatable
+    #[arg(long, value_name = "REGEX", value_parser = parse_bytes_regex)]
+    pub redact_pattern: Vec<regex::bytes::Regex>,
+
+    /// Text the `redact` action puts in place of every match
+    #[arg(long, value_name = "TEXT", default_value = "[REDACTED]")]
+    pub mask: String,
+
+    /// Show a scan for the data the `redact` action masks in the window, to pick the files to clean from its findings
+    #[arg(long)]
+    pub discover: bool,
+
+    /// Manifest the `hash` action appends checksums to [default: SHA256SUMS in the data directory]
+    #[arg(long, value_name = "FILE")]
+    pub checksums: Option<PathBuf>,
+
+    /// Rename files by replacing the matches of a regular expression in their name instead of renaming them to File{index}
+    #[arg(long, value_name = "REGEX", value_parser = parse_regex, requires = "replace", conflicts_with = "pseudonym_key")]
+    pub find: Option<Regex>,
+
+    /// Replacement for --find: "$1" or "${name}" for its groups, {n} or {n:3} for a counter, {date} for the modification date
+    #[arg(long, value_name = "TEMPLATE", requires = "find")]
+    pub replace: Option<String>,
+
+    /// Change the case of the new names, extensions excepted
+    #[arg(long, value_enum, default_value_t = NameCase::Keep, conflicts_with = "pseudonym_key")]
+    pub case: NameCase,
+
+    /// Replace non-ASCII characters in the new names with their closest ASCII spelling
+    #[arg(long, conflicts_with = "pseudonym_key")]
+    pub transliterate: bool,
+
+    /// First value of the {n} counter of --replace
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub counter_start: usize,
+
+    /// Rename files to a keyed hash of their name instead of File{index}, with the key read from FILE
+    #[arg(long, value_name = "FILE", value_parser = parse_key)]
+    pub pseudonym_key: Option<Key>,
+
+    /// Encrypted file the renames made with --pseudonym-key are appended to [default: MAPPING in the data directory]
+    #[arg(long, value_name = "FILE", requires = "pseudonym_key")]
+    pub mapping: Option<PathBuf>,
+
+    /// Write the renames recorded in --mapping to FILE as `original<TAB>new` lines and exit
+    #[arg(long, value_name = "FILE", requires = "pseudonym_key")]
+    pub reveal_mapping: Option<PathBuf>,
+
+    /// Take the files from a list, one path per line ("-" reads stdin). Relative paths are resolved against the selected folder
+    #[arg(long, value_name = "FILE", group = "source")]
+    pub files_from: Option<PathBuf>,
+
+    /// Paths in --files-from are separated by NUL, as printed by `find -print0`
+    #[arg(short = '0', long, requires = "files_from")]
+    pub null: bool,
+
+    /// Take the files matching a glob expression, relative to the selected folder
+    #[arg(long, value_name = "PATTERN", group = "source")]
+    pub glob: Option<String>,
+
+    /// Take the untracked files of the git repository in the selected folder
+    #[arg(long, group = "source")]
+    pub git_untracked: bool,
+
+    /// Take the files recorded by a previous --save-scan
+    #[arg(long, value_name = "FILE", group = "source")]
+    pub from_scan: Option<PathBuf>,
+
+    /// Also process the files in the subdirectories of the selected folder
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Rename the subdirectories of the selected folder to Dir{index}, deepest first, once their files are processed. Implies --recursive
+    #[arg(long)]
+    pub rename_dirs: bool,
+
+    /// Times of the directories after --rename-dirs: "preserve", "epoch", "random" (within the last year) or seconds since the epoch
+    #[arg(long, value_name = "WHEN", default_value = "preserve", value_parser = parse_directory_times, requires = "rename_dirs")]
+    pub dir_times: DirectoryTimes,
+
+    /// Move every file of the selected folder and its subdirectories to the selected folder, then remove the directories left empty. Implies --recursive
+    #[arg(long, conflicts_with = "pseudonym_key")]
+    pub flatten: bool,
+
+    /// File the original paths of flattened files are appended to, as `original<TAB>new` lines [default: FLATTENED in the data directory]
+    #[arg(long, value_name = "FILE", requires = "flatten")]
+    pub flatten_manifest: Option<PathBuf>,
+
+    /// Record the files found by the job, to process them again with --from-scan
+    #[arg(long, value_name = "FILE")]
+    pub save_scan: Option<PathBuf>,
+
+    /// Number of files processed in parallel, one at a time like jobs started without the command line
+    #[arg(short, long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: u16,
+
+    /// Backend used for reads, writes, renames, fsync and removals
+    #[arg(long, value_enum, default_value_t = IoKind::Tokio)]
+    pub io: IoKind,
+
+    /// Maximum bytes read or written per second, with an optional K, M or G suffix. Can be changed in the window while a job runs
+    #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+    pub max_bytes_per_second: Option<u64>,
+
+    /// Maximum reads, writes, renames, fsyncs and removals per second. Can be changed in the window while a job runs
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_ops_per_second: Option<u64>,
+
+    /// Only start files while the load average and disk utilization are low. Can be changed in the window while a job runs
+    #[arg(long)]
+    pub when_idle: bool,
+
+    /// Leave alone files another process has open (Linux only)
+    #[arg(long)]
+    pub skip_open: bool,
+
+    /// Leave alone files modified within the last SECS seconds
+    #[arg(long, value_name = "SECS")]
+    pub stable_for: Option<u64>,
+
+    /// Retry files modified within --stable-for later in the job instead of skipping them
+    #[arg(long, requires = "stable_for")]
+    pub defer_unstable: bool,
+
+    /// Write what happened to every file to a JSON report
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+
+    /// Times the `strip-metadata` action sets: "epoch", "random" (within the last year) or seconds since the epoch
+    #[arg(long, value_name = "WHEN", default_value = "epoch", value_parser = parse_timestamps)]
+    pub scrub_times: Timestamps,
+
+    /// Permission bits the `strip-metadata` action sets, in octal
+    #[arg(long, value_name = "MODE", default_value = "644", value_parser = parse_mode, conflicts_with = "keep_mode")]
+    pub scrub_mode: u32,
+
+    /// Keep the permission bits when stripping metadata
+    #[arg(long)]
+    pub keep_mode: bool,
+
+    /// Keep extended attributes and ACLs when stripping metadata
+    #[arg(long)]
+    pub keep_xattrs: bool,
+}
+
+/// Content of a key file, kept out of debug output.
+#[derive(Clone)]
+pub struct Key(pub Arc<[u8]>);
+
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum IoKind {
+    /// The tokio::fs blocking thread pool
+    #[default]
+    Tokio,
+    /// A shared io_uring
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    IoUring,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ActionKind {
+    Rename,
+    Truncate,
+    Overwrite,
+    Replace,
+    KeepTail,
+    Compress,
+    Redact,
+    /// Record the data `redact` would mask in the report, changing nothing
+    Discover,
+    Delete,
+    Hash,
+    StripMetadata,
+    /// Remove the EXIF, XMP and IPTC blocks of JPEG, PNG, TIFF and WebP images
+    StripImageMetadata,
+    /// Remove the author, company, revision count, comments and custom properties of
+    /// Office, OpenDocument and PDF documents
+    StripDocumentMetadata,
+    /// Remove the ID3 tags, Vorbis comments, FLAC comment and picture blocks and MP4
+    /// metadata atoms of audio and video files
+    StripMediaTags,
+    Organize,
+    Sanitize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum NameCase {
+    #[default]
+    Keep,
+    Lower,
+    Upper,
+    /// Every word capitalized
+    Title,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OrganizeBy {
+    /// pdf/, jpg/, ...
+    #[default]
+    Extension,
+    /// image/png/, text/plain/, ...
+    Mime,
+    /// YYYY/MM/ of the modification time
+    Date,
+    /// 0-1K/, 1K-1M/, 1M-100M/, 100M-1G/ and 1G+/
+    Size,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DetectorKind {
+    Email,
+    /// Card numbers passing the Luhn check
+    Card,
+    /// IBANs with a valid checksum
+    Iban,
+    /// AWS access key IDs and secret access keys
+    Aws,
+    /// PEM private key blocks
+    PrivateKey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Compression {
+    /// {name}.gz
+    Gzip,
+    /// {name}.zst
+    #[default]
+    Zstd,
+    /// {name}.xz
+    Xz,
+}
+
+impl Args {
+    /// Parses the command line like [`Parser::parse`], also exiting with a usage error
+    /// for values the other arguments rule out.
+    pub fn parse_checked() -> Self {
+        let args = Self::parse();
+        if let Err(err) = args.check() {
+            err.exit();
+        }
+
+        args
+    }
+
+    /// Rejects a `--level` the codec of `--compression` does not have.
+    fn check(&self) -> Result<(), clap::Error> {
+        let levels = self.codec().levels();
+
+        match self.level {
+            Some(level) if levels.contains(&level).not() => Err(Self::command().error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "invalid value '{level}' for '--level <LEVEL>': {} takes levels {} to {}",
+                    self.codec().extension(),
+                    levels.start(),
+                    levels.end()
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Pipeline composed from `--actions`, if any were given.
+    pub fn pipeline(&self) -> Option<Pipeline> {
+        if self.actions.is_empty() {
+            return None;
+        }
+
+        let pipeline = self
+            .actions
+            .iter()
+            .fold(Pipeline::new(), |pipeline, kind| match kind {
+                ActionKind::Rename => pipeline.then(self.rename()),
+                ActionKind::Truncate => pipeline.then(Truncate),
+                ActionKind::Overwrite => pipeline.then(Overwrite),
+                ActionKind::Replace => pipeline.then(Replace::new(self.placeholder())),
+                ActionKind::KeepTail => pipeline.then(self.keep_tail()),
+                ActionKind::Compress => pipeline.then(self.compress()),
+                ActionKind::Redact => pipeline.then(self.redact()),
+                ActionKind::Discover => {
+                    pipeline.then(Discover::new(self.redact(), Default::default()))
+                }
+                ActionKind::Delete => pipeline.then(Delete),
+                ActionKind::Hash => pipeline.then(Hash::new(self.checksums())),
+                ActionKind::StripMetadata => pipeline.then(self.strip_metadata()),
+                ActionKind::StripImageMetadata => pipeline.then(StripImageMetadata),
+                ActionKind::StripDocumentMetadata => pipeline.then(StripDocumentMetadata),
+                ActionKind::StripMediaTags => pipeline.then(StripMediaTags),
+                ActionKind::Organize => pipeline.then(self.organize()),
+                ActionKind::Sanitize => pipeline.then(
+                    Sanitize::default()
+                        .with_max_bytes(self.max_name_bytes.into())
+                        .with_replacement(self.replacement),
+                ),
+            });
+
+        Some(pipeline)
+    }
+
+    /// The `rename` action, naming files with `--pseudonym-key` or the rename pattern
+    /// if given.
+    pub fn rename(&self) -> Rename {
+        let rename = match (&self.pseudonym_key, self.rename_pattern()) {
+            (Some(Key(key)), _) => Rename::new(Naming::Pseudonym(Arc::new(Pseudonyms::new(
+                key,
+                self.mapping(),
+            )))),
+            (None, Some(pattern)) => Rename::new(Naming::Pattern(Arc::new(pattern))),
+            (None, None) => Rename::default(),
+        };
+
+        match self.flatten {
+            true => rename.with_flattening(self.flatten_manifest()),
+            false => rename,
+        }
+    }
+
+    /// Manifest of the `hash` action.
+    pub fn checksums(&self) -> PathBuf {
+        self.checksums
+            .clone()
+            .unwrap_or_else(|| data_dir().join("SHA256SUMS"))
+    }
+
+    /// Mapping file of the renames made with `--pseudonym-key`.
+    pub fn mapping(&self) -> PathBuf {
+        self.mapping
+            .clone()
+            .unwrap_or_else(|| data_dir().join("MAPPING"))
+    }
+
+    /// Where the original paths of flattened files are recorded.
+    pub fn flatten_manifest(&self) -> PathBuf {
+        self.flatten_manifest
+            .clone()
+            .unwrap_or_else(|| data_dir().join("FLATTENED"))
+    }
+
+    /// Files the actions selected on the command line write to, which jobs must not
+    /// process even when they are inside the job folder.
+    pub fn side_files(&self) -> Vec<PathBuf> {
+        let mut side_files = Vec::new();
+
+        if self.actions.contains(&ActionKind::Hash) {
+            side_files.push(self.checksums());
+        }
+        if self.pseudonym_key.is_some() {
+            side_files.push(self.mapping());
+        }
+        if self.flatten {
+            side_files.push(self.flatten_manifest());
+        }
+
+        side_files
+    }
+
+    /// The `keep-tail` action as configured on the command line.
+    pub fn keep_tail(&self) -> KeepTail {
+        let keep = match (self.tail_lines, self.tail_bytes) {
+            (_, Some(bytes)) => Tail::Bytes(bytes),
+            (lines, None) => Tail::Lines(lines.unwrap_or(1000)),
+        };
+
+        KeepTail::new(keep).with_archive(self.archive_head)
+    }
+
+    /// The `compress` action as configured on the command line.
+    pub fn compress(&self) -> Compress {
+        match self.level {
+            Some(level) => Compress::new(self.codec()).with_level(level),
+            None => Compress::new(self.codec()),
+        }
+    }
+
+    fn codec(&self) -> Codec {
+        match self.compression {
+            Compression::Gzip => Codec::Gzip,
+            Compression::Zstd => Codec::Zstd,
+            Compression::Xz => Codec::Xz,
+        }
+    }
+
+    /// The `redact` action as configured on the command line.
+    pub fn redact(&self) -> Redact {
+        let detectors = match (self.no_detectors, self.detectors.is_empty()) {
+            (true, _) => Vec::new(),
+            (false, true) => Detector::ALL.to_vec(),
+            (false, false) => self
+                .detectors
+                .iter()
+                .map(|kind| match kind {
+                    DetectorKind::Email => Detector::Email,
+                    DetectorKind::Card => Detector::CreditCard,
+                    DetectorKind::Iban => Detector::Iban,
+                    DetectorKind::Aws => Detector::AwsKey,
+                    DetectorKind::PrivateKey => Detector::PrivateKey,
+                })
+                .collect(),
+        };
+
+        self.redact_pattern.iter().fold(
+            Redact::default()
+                .with_detectors(detectors)
+                .with_mask(self.mask.clone()),
+            |redact, pattern| redact.with_pattern(pattern.clone()),
+        )
+    }
+
+    /// What the `replace` action writes.
+    pub fn placeholder(&self) -> Placeholder {
+        if let Some(template) = &self.placeholder_file {
+            return Placeholder::Template(template.clone());
+        }
+
+        match (&self.placeholder, self.random_placeholder) {
+            (_, true) => Placeholder::Random,
+            (Some(text), false) => Placeholder::Text(text.clone()),
+            (None, false) => Placeholder::Text("REDACTED".to_string()),
+        }
+    }
+
+    /// Naming by `--find`, `--case` and `--transliterate`, if any of them was given.
+    pub fn rename_pattern(&self) -> Option<RenamePattern> {
+        if self.find.is_none() && self.case == NameCase::Keep && self.transliterate.not() {
+            return None;
+        }
+
+        let case = match self.case {
+            NameCase::Keep => Case::Keep,
+            NameCase::Lower => Case::Lower,
+            NameCase::Upper => Case::Upper,
+            NameCase::Title => Case::Title,
+        };
+        let mut pattern = RenamePattern::new()
+            .with_case(case)
+            .with_transliteration(self.transliterate)
+            .with_counter_start(self.counter_start);
+        if let (Some(find), Some(replace)) = (&self.find, &self.replace) {
+            pattern = pattern.with_replacement(find.clone(), replace.clone());
+        }
+
+        Some(pattern)
+    }
+
+    /// The `organize` action as configured on the command line.
+    pub fn organize(&self) -> Organize {
+        let rule = match self.organize_by {
+            OrganizeBy::Extension => SortRule::Extension,
+            OrganizeBy::Mime => SortRule::Mime,
+            OrganizeBy::Date => SortRule::Date,
+            OrganizeBy::Size => SortRule::Size,
+        };
+
+        Organize::new(rule).with_dry_run(self.dry_run)
+    }
+
+    /// The `strip-metadata` action as configured on the command line.
+    pub fn strip_metadata(&self) -> StripMetadata {
+        StripMetadata::default()
+            .with_times(self.scrub_times)
+            .with_xattrs(self.keep_xattrs.not())
+            .with_mode(self.keep_mode.not().then_some(self.scrub_mode))
+    }
+
+    /// Source selected on the command line for a job in `root`, if any.
+    pub fn source(&self, root: &Path) -> Option<Arc<dyn FileSource>> {
+        let root = root.to_path_buf();
+
+        if let Some(list) = &self.files_from {
+            let delimiter = if self.null { b'\0' } else { b'\n' };
+            return Some(Arc::new(ListSource::new(list.clone(), delimiter, root)));
+        }
+        if let Some(pattern) = &self.glob {
+            return Some(Arc::new(GlobSource::new(pattern.clone(), root)));
+        }
+        if self.git_untracked {
+            return Some(Arc::new(GitUntrackedSource::new(root)));
+        }
+        if let Some(scan) = &self.from_scan {
+            return Some(Arc::new(ScanSource::new(scan.clone())));
+        }
+        if self.recursive || self.rename_dirs || self.flatten {
+            return Some(Arc::new(DirectorySource::recursive(root)));
+        }
+
+        None
+    }
+
+    pub fn io(&self) -> Arc<dyn IoBackend> {
+        match self.io {
+            IoKind::Tokio => Arc::new(TokioBackend),
+            #[cfg(all(feature = "io-uring", target_os = "linux"))]
+            IoKind::IoUring => match UringBackend::new() {
+                Ok(backend) => Arc::new(backend),
+                Err(err) => {
+                    eprintln!("io_uring is unavailable, falling back to tokio: {err}");
+                    Arc::new(TokioBackend)
+                }
+            },
+        }
+    }
+
+    pub fn limits(&self) -> Limits {
+        Limits::new(
+            self.max_bytes_per_second,
+            self.max_ops_per_second,
+            self.when_idle,
+        )
+    }
+
+    /// Renaming of the subdirectories requested with `--rename-dirs`, if any.
+    pub fn directory_renamer(&self) -> Option<DirectoryRenamer> {
+        self.rename_dirs
+            .then(|| DirectoryRenamer::new(self.dir_times))
+    }
+
+    pub fn busy_check(&self) -> BusyCheck {
+        BusyCheck::new(
+            self.skip_open,
+            self.stable_for.map(Duration::from_secs),
+            self.defer_unstable,
+        )
+    }
+
+    pub fn describe_actions(&self) -> String {
+        self.actions
+            .iter()
+            .filter_map(|kind| kind.to_possible_value())
+            .map(|value| value.get_name().to_string())
+            .join(", ")
+    }
+}
+
+/// Directory of the files kept across jobs, outside of any job folder:
+/// `%APPDATA%\rutabaga` on Windows, `~/Library/Application Support/rutabaga` on macOS
+/// and `$XDG_DATA_HOME/rutabaga` or `~/.local/share/rutabaga` elsewhere.
+fn data_dir() -> PathBuf {
+    let var = |name| std::env::var_os(name).map(PathBuf::from);
+    let home = || var("HOME");
+
+    let base = match std::env::consts::OS {
+        "windows" => var("APPDATA"),
+        "macos" => home().map(|home| home.join("Library/Application Support")),
+        _ => var("XDG_DATA_HOME")
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| home().map(|home| home.join(".local/share"))),
+    };
+
+    base.unwrap_or_else(std::env::temp_dir).join("rutabaga")
+}
+
+/// Parses a byte count such as `512`, `64K`, `50M` or `1G`.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let (digits, unit) = match value.find(|c: char| c.is_ascii_digit().not()) {
+        None => (value, ""),
+        Some(i) => value.split_at(i),
+    };
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return Err(format!("unknown unit `{unit}`")),
+    };
+
+    let count = digits.parse::<u64>().map_err(|err| err.to_string())?;
+    count
+        .checked_mul(multiplier)
+        .ok_or_else(|| "the size is too large".to_string())
+}
+
+/// Parses a byte rate like [`parse_size`], which must not be zero.
+fn parse_rate(value: &str) -> Result<u64, String> {
+    match parse_size(value)? {
+        0 => Err("the rate must be greater than zero".to_string()),
+        rate => Ok(rate),
+    }
+}
+
+/// Parses `epoch`, `random` or a number of seconds since the epoch.
+fn parse_timestamps(value: &str) -> Result<Timestamps, String> {
+    match value {
+        "epoch" => Ok(Timestamps::Fixed(SystemTime::UNIX_EPOCH)),
+        "random" => Ok(Timestamps::Random),
+        seconds => match seconds.parse() {
+            Ok(seconds) => Ok(Timestamps::Fixed(
+                SystemTime::UNIX_EPOCH + Duration::from_secs(seconds),
+            )),
+            Err(_) => Err("expected epoch, random or a number of seconds".to_string()),
+        },
+    }
+}
+
+/// Parses `preserve` or any of the values of [`parse_timestamps`].
+fn parse_directory_times(value: &str) -> Result<DirectoryTimes, String> {
+    match value {
+        "preserve" => Ok(DirectoryTimes::Preserve),
+        value => parse_timestamps(value).map(DirectoryTimes::Set),
+    }
+}
+
+/// Parses a character that is itself allowed in file names everywhere.
+fn parse_replacement(value: &str) -> Result<char, String> {
+    let mut chars = value.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_control().not() && "<>:\"/\\|?*. ".contains(c).not() => Ok(c),
+        _ => Err("expected a single character allowed in file names".to_string()),
+    }
+}
+
+fn parse_regex(value: &str) -> Result<Regex, String> {
+    Regex::new(value).map_err(|err| err.to_string())
+}
+
+fn parse_bytes_regex(value: &str) -> Result<regex::bytes::Regex, String> {
+    regex::bytes::Regex::new(value).map_err(|err| err.to_string())
+}
+
+fn parse_key(value: &str) -> Result<Key, String> {
+    match std::fs::read(value) {
+        Ok(key) if key.is_empty() => Err("the key file is empty".to_string()),
+        Ok(key) => Ok(Key(key.into())),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn parse_mode(value: &str) -> Result<u32, String> {
+    match u32::from_str_radix(value, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err("expected octal permission bits such as 644".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        Args::try_parse_from(std::iter::once("rutabaga").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn arguments_are_consistent() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn placeholders_exclude_each_other() {
+        let text = parse(&["--placeholder", "gone"]).unwrap();
+        assert!(matches!(text.placeholder(), Placeholder::Text(text) if text == "gone"));
+        assert!(
+            matches!(parse(&[]).unwrap().placeholder(), Placeholder::Text(text) if text == "REDACTED")
+        );
+        assert!(matches!(
+            parse(&["--random-placeholder"]).unwrap().placeholder(),
+            Placeholder::Random
+        ));
+        assert!(parse(&["--placeholder", "gone", "--random-placeholder"]).is_err());
+    }
+
+    #[test]
+    fn side_files_default_to_the_data_directory() {
+        let key = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(key.path(), "key").unwrap();
+        let key = key.path().to_str().unwrap();
+
+        let args = parse(&["--actions", "hash", "--pseudonym-key", key]).unwrap();
+        assert_eq!(
+            args.side_files(),
+            [data_dir().join("SHA256SUMS"), data_dir().join("MAPPING")]
+        );
+        let args = parse(&["--flatten"]).unwrap();
+        assert_eq!(args.side_files(), [data_dir().join("FLATTENED")]);
+        assert!(data_dir().is_absolute());
+
+        // The manifest would list the pseudonyms next to the original names in clear.
+        let args = parse(&["--pseudonym-key", key, "--flatten"]);
+        assert!(args.is_err(), "--flatten conflicts with --pseudonym-key");
+
+        let args = parse(&["--checksums", "sums", "--mapping", "map"]);
+        assert!(args.is_err(), "--mapping requires --pseudonym-key");
+        let args = parse(&["--actions", "truncate", "--checksums", "sums"]).unwrap();
+        assert_eq!(args.checksums(), PathBuf::from("sums"));
+        assert!(args.side_files().is_empty());
+    }
+
+    #[test]
+    fn files_are_processed_one_at_a_time_by_default() {
+        assert_eq!(parse(&[]).unwrap().jobs, 1);
+        assert_eq!(parse(&["-j", "8"]).unwrap().jobs, 8);
+        assert!(parse(&["--jobs", "0"]).is_err());
+    }
+
+    #[test]
+    fn sizes_take_units_and_rates_must_be_positive() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("64k"), Ok(64 << 10));
+        assert_eq!(parse_size("50MB"), Ok(50 << 20));
+        assert_eq!(parse_size("0"), Ok(0));
+        assert!(parse_size("1T").is_err());
+        assert!(parse_size("99999999999G").is_err());
+
+        let args = parse(&["--tail-bytes", "0"]).unwrap();
+        assert_eq!(args.tail_bytes, Some(0));
+        let err = parse(&["--max-bytes-per-second", "0"]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("the rate must be greater than zero"));
+    }
+
+    #[test]
+    fn levels_must_suit_the_codec() {
+        let level = |args: &[&str]| parse(args).map(|args| args.check().map(|_| args.level));
+
+        assert_eq!(level(&["--level", "22"]).unwrap().unwrap(), Some(22));
+        assert!(level(&["--level", "23"]).is_err());
+        assert!(level(&["--compression", "gzip", "--level", "9"])
+            .unwrap()
+            .is_ok());
+        assert!(level(&["--compression", "xz", "--level", "10"])
+            .unwrap()
+            .is_err());
+        assert!(level(&["--level", "0"]).unwrap().is_err());
+    }
+}
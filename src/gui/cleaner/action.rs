@@ -0,0 +1,1191 @@
+use crate::gui::cleaner::io::{Fill, IoBackend};
+use crate::gui::cleaner::pattern::{Conflict, RenamePattern};
+use crate::gui::cleaner::pseudonym::Pseudonyms;
+use crate::gui::cleaner::report::FileOutcome;
+use anyhow::Context;
+use async_trait::async_trait;
+use parking_lot::Mutex as SyncMutex;
+use rand::Rng;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// A single step applied to every file of a job.
+///
+/// Actions run in pipeline order. An action that moves the file must update
+/// [`FileEntry::path`] so that the following actions see the new location.
+#[async_trait]
+pub trait FileAction: Debug + Send + Sync {
+    /// Past-tense label shown next to the action's counter, e.g. "Renamed".
+    fn label(&self) -> &str;
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome;
+}
+
+/// Job-wide state shared by all actions.
+#[derive(Debug, Clone)]
+pub struct ActionContext {
+    pub root: PathBuf,
+    pub outputs: Arc<Outputs>,
+    pub io: Arc<dyn IoBackend>,
+}
+
+/// Paths created by the job while its files are still being enumerated.
+///
+/// A directory listing may or may not return entries added after it started, so
+/// actions that move files claim the new path here first and the enumeration
+/// drops it instead of handing the same file to the pipeline twice.
+#[derive(Debug, Default)]
+pub struct Outputs(SyncMutex<HashSet<PathBuf>>);
+
+impl Outputs {
+    pub fn remove(&self, path: &Path) -> bool {
+        self.0.lock().remove(path)
+    }
+
+    /// Like [`free_path`], but also passes over the paths other files of the job are
+    /// about to be moved to, and registers the path returned.
+    ///
+    /// Files moved to the same directory at the same time get different names this way.
+    /// The file system is checked before taking the lock, so that a slow disk does not
+    /// hold up the other workers; a name is only handed out if no one claimed it since.
+    pub fn claim(&self, dir: &Path, stem: &str, extension: Option<&str>) -> PathBuf {
+        candidates(dir, stem, extension)
+            .find(|path| path.exists().not() && self.0.lock().insert(path.clone()))
+            .expect("there are infinitely many candidates")
+    }
+
+    /// Registers `path` as taken without checking the file system, returning whether it
+    /// was free.
+    pub fn insert(&self, path: PathBuf) -> bool {
+        self.0.lock().insert(path)
+    }
+}
+
+/// The file an action is applied to.
+#[derive(Debug)]
+pub struct FileEntry {
+    /// Position of the file in the job, used for sequential naming.
+    pub index: usize,
+    pub path: PathBuf,
+    /// Size of the file when the job reached it, in bytes.
+    pub len: u64,
+    /// What the actions found out about the file, added to its report.
+    pub details: Map<String, Value>,
+    /// Bytes of the file already reported to `counters`.
+    done: AtomicU64,
+    counters: Arc<Counters>,
+}
+
+impl FileEntry {
+    pub fn new(index: usize, path: PathBuf, len: u64, counters: Arc<Counters>) -> Self {
+        Self {
+            index,
+            path,
+            len,
+            details: Map::new(),
+            done: AtomicU64::new(0),
+            counters,
+        }
+    }
+
+    /// Reports that an action has gone through the first `position` bytes of the file.
+    ///
+    /// Actions making several passes over the content only count the furthest one.
+    pub fn advance(&self, position: u64) {
+        let position = position.min(self.len);
+        let done = self.done.fetch_max(position, Ordering::SeqCst);
+
+        if position > done {
+            self.counters
+                .partial
+                .fetch_add(position - done, Ordering::SeqCst);
+        }
+    }
+
+    /// Reports that the file was replaced with an archive of `len` bytes.
+    pub fn compressed(&self, len: u64) {
+        self.counters
+            .compressed_from
+            .fetch_add(self.len, Ordering::SeqCst);
+        self.counters.compressed_to.fetch_add(len, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug)]
+pub enum ActionOutcome {
+    /// The action changed the file; the next action runs.
+    Applied,
+    /// The action does not apply to the file; the next action runs.
+    Skipped,
+    /// The action is done with the file and the remaining actions must not run, as in
+    /// a dry run.
+    Stop,
+    /// The action failed; the remaining actions are not run for this file.
+    Failed(anyhow::Error),
+}
+
+impl<E: Into<anyhow::Error>> From<Result<(), E>> for ActionOutcome {
+    fn from(result: Result<(), E>) -> Self {
+        match result {
+            Ok(_) => ActionOutcome::Applied,
+            Err(err) => ActionOutcome::Failed(err.into()),
+        }
+    }
+}
+
+/// Ordered list of actions applied to every file of a job.
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    actions: Vec<Arc<dyn FileAction>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn then(mut self, action: impl FileAction + 'static) -> Self {
+        self.actions.push(Arc::new(action));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn labels(&self) -> Vec<String> {
+        self.actions
+            .iter()
+            .map(|action| action.label().to_string())
+            .collect()
+    }
+
+    /// Runs the actions on `entry`, counting every applied action in `completed`.
+    pub async fn run(
+        &self,
+        ctx: &ActionContext,
+        entry: &mut FileEntry,
+        completed: &Counters,
+    ) -> FileOutcome {
+        for (action, completed) in self.actions.iter().zip(completed.completed.iter()) {
+            match action.apply(ctx, entry).await {
+                ActionOutcome::Applied => {
+                    completed.fetch_add(1, Ordering::SeqCst);
+                }
+                ActionOutcome::Skipped => {}
+                ActionOutcome::Stop => {
+                    completed.fetch_add(1, Ordering::SeqCst);
+                    break;
+                }
+                ActionOutcome::Failed(err) => {
+                    return FileOutcome::Failed {
+                        action: action.label().to_string(),
+                        error: format!("{err:#}"),
+                    };
+                }
+            }
+        }
+
+        FileOutcome::Processed
+    }
+}
+
+/// Progress of a job, shared by its workers.
+#[derive(Debug)]
+pub struct Counters {
+    /// Number of files each action of the pipeline has been applied to.
+    completed: Vec<AtomicUsize>,
+    /// Size of the files the pipeline has finished with, in bytes.
+    bytes: AtomicU64,
+    /// Bytes already gone through in the files still being processed.
+    partial: AtomicU64,
+    /// Number of files left untouched, see [`crate::gui::cleaner::busy`].
+    skipped: AtomicUsize,
+    /// Size of the files replaced with archives, in bytes.
+    compressed_from: AtomicU64,
+    /// Size of those archives, in bytes.
+    compressed_to: AtomicU64,
+    /// The file most recently handed to the pipeline.
+    current: SyncMutex<Option<PathBuf>>,
+}
+
+impl Counters {
+    pub fn new(len: usize) -> Self {
+        Self {
+            completed: (0..len).map(|_| AtomicUsize::new(0)).collect(),
+            bytes: AtomicU64::new(0),
+            partial: AtomicU64::new(0),
+            skipped: AtomicUsize::new(0),
+            compressed_from: AtomicU64::new(0),
+            compressed_to: AtomicU64::new(0),
+            current: Default::default(),
+        }
+    }
+
+    pub fn start(&self, path: &Path) {
+        *self.current.lock() = Some(path.to_path_buf());
+    }
+
+    /// Counts a file of `bytes` bytes as left untouched.
+    pub fn skip(&self, bytes: u64) {
+        self.skipped.fetch_add(1, Ordering::SeqCst);
+        self.bytes.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    /// Counts the whole of `entry` as processed, whatever its actions went through.
+    pub fn finish(&self, entry: &FileEntry) {
+        self.bytes.fetch_add(entry.len, Ordering::SeqCst);
+        self.partial
+            .fetch_sub(entry.done.load(Ordering::SeqCst), Ordering::SeqCst);
+    }
+
+    pub fn snapshot(&self) -> Vec<usize> {
+        self.completed
+            .iter()
+            .map(|counter| counter.load(Ordering::SeqCst))
+            .collect()
+    }
+
+    /// Bytes processed so far, including the progress within files still in flight.
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::SeqCst) + self.partial.load(Ordering::SeqCst)
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.skipped.load(Ordering::SeqCst)
+    }
+
+    /// Size of the files compressed so far and of their archives.
+    pub fn compression(&self) -> (u64, u64) {
+        (
+            self.compressed_from.load(Ordering::SeqCst),
+            self.compressed_to.load(Ordering::SeqCst),
+        )
+    }
+
+    pub fn current(&self) -> Option<PathBuf> {
+        self.current.lock().clone()
+    }
+}
+
+/// Returns the first path of `dir/{stem}.{extension}`, `dir/{stem}(1).{extension}`, ...
+/// that does not exist yet.
+pub fn free_path(dir: &Path, stem: &str, extension: Option<&str>) -> PathBuf {
+    candidates(dir, stem, extension)
+        .find(|path| path.exists().not())
+        .expect("there are infinitely many candidates")
+}
+
+/// `stem.extension`, `stem(1).extension`, `stem(2).extension`, ... in `dir`.
+pub fn candidates<'a>(
+    dir: &'a Path,
+    stem: &'a str,
+    extension: Option<&'a str>,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    (0..).map(move |i| {
+        let name = if i == 0 {
+            stem.to_string()
+        } else {
+            format!("{stem}({i})")
+        };
+
+        match extension {
+            None => dir.join(name),
+            Some(extension) => dir.join(format!("{name}.{extension}")),
+        }
+    })
+}
+
+/// Renames the file in its own directory, see [`Naming`], or moves it to the job folder
+/// when flattening.
+#[derive(Debug, Clone, Default)]
+pub struct Rename {
+    naming: Naming,
+    /// Where the original paths of flattened files are recorded.
+    flatten: Option<Arc<Manifest>>,
+}
+
+/// How [`Rename`] picks the new name of a file.
+#[derive(Debug, Clone, Default)]
+pub enum Naming {
+    /// `File{index}.txt`.
+    #[default]
+    Sequential,
+    /// A keyed hash of the original name, keeping the extension. The renames are
+    /// recorded to an encrypted mapping file once done.
+    ///
+    /// A pseudonym that is already taken, by the file of an earlier job for instance,
+    /// gets a `(1)` suffix like the other names; the mapping records the name used.
+    Pseudonym(Arc<Pseudonyms>),
+    /// Find and replace, case conversion and transliteration of the original name.
+    /// Files the pattern leaves unchanged keep their name.
+    ///
+    /// The names files had when the job started are not handed out again once their
+    /// file is renamed, so that the names given do not depend on the order the files are
+    /// processed in and match the [`super::pattern::preview`].
+    Pattern(Arc<RenamePattern>),
+}
+
+impl Rename {
+    pub fn new(naming: Naming) -> Self {
+        Self {
+            naming,
+            flatten: None,
+        }
+    }
+
+    /// Moves every file to the job folder, appending `original<TAB>new` lines with paths
+    /// relative to the job folder to `manifest`.
+    pub fn with_flattening(mut self, manifest: PathBuf) -> Self {
+        self.flatten = Some(Arc::new(Manifest::new(manifest)));
+        self
+    }
+}
+
+#[async_trait]
+impl FileAction for Rename {
+    fn label(&self) -> &str {
+        "Renamed"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        let dir = match self.flatten {
+            Some(_) => ctx.root.as_path(),
+            None => entry.path.parent().unwrap_or(&ctx.root),
+        };
+        let new_path = match &self.naming {
+            Naming::Sequential => {
+                let stem = format!("File{}", entry.index);
+                ctx.outputs.claim(dir, &stem, Some("txt"))
+            }
+            Naming::Pseudonym(pseudonyms) => {
+                let name = entry.path.file_name().unwrap_or_default();
+                let (stem, extension) = pseudonyms.name(name);
+                ctx.outputs.claim(dir, &stem, extension.as_deref())
+            }
+            Naming::Pattern(pattern) => {
+                let name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+                let modified = match tokio::fs::metadata(&entry.path).await {
+                    Ok(metadata) => metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    Err(err) => return ActionOutcome::Failed(err.into()),
+                };
+
+                let new_name = pattern.rename(&name, entry.index, modified);
+                if let Some(conflict) = Conflict::check(&new_name) {
+                    return ActionOutcome::Failed(anyhow::anyhow!("{new_name:?}: {conflict}"));
+                }
+                if new_name == name && entry.path.parent() == Some(dir) {
+                    return ActionOutcome::Skipped;
+                }
+
+                // Only the case changes, on a file system that ignores it.
+                let target = dir.join(&new_name);
+                if same_file(&entry.path, &target) {
+                    ctx.outputs.insert(target.clone());
+                    target
+                } else {
+                    let new_name = Path::new(&new_name);
+                    let stem = new_name.file_stem().unwrap_or_default().to_string_lossy();
+                    let extension = new_name
+                        .extension()
+                        .map(|extension| extension.to_string_lossy());
+                    ctx.outputs.claim(dir, &stem, extension.as_deref())
+                }
+            }
+        };
+
+        let original = match move_to(ctx, entry, new_path).await {
+            Ok(original) => original,
+            Err(err) => return ActionOutcome::Failed(err),
+        };
+        if let Naming::Pattern(_) = &self.naming {
+            ctx.outputs.insert(original.clone());
+        }
+        if let Naming::Pseudonym(pseudonyms) = &self.naming {
+            let from = original.strip_prefix(&ctx.root).unwrap_or(&original);
+            let to = entry.path.strip_prefix(&ctx.root).unwrap_or(&entry.path);
+            if let Err(err) = pseudonyms.record(from, to).await {
+                // Moved back, so that a file is never left renamed without a way back.
+                return match ctx.io.rename(&entry.path, &original).await {
+                    Ok(_) => {
+                        let renamed = std::mem::replace(&mut entry.path, original);
+                        ctx.outputs.remove(&renamed);
+                        ActionOutcome::Failed(err)
+                    }
+                    Err(undo) => ActionOutcome::Failed(
+                        err.context(format!("moving the file back failed: {undo}")),
+                    ),
+                };
+            }
+        }
+        if let Some(manifest) = &self.flatten {
+            let from = original.strip_prefix(&ctx.root).unwrap_or(&original);
+            let to = entry.path.strip_prefix(&ctx.root).unwrap_or(&entry.path);
+            let line = format!("{}\t{}\n", from.display(), to.display());
+
+            return manifest.append(line.as_bytes()).await.into();
+        }
+
+        ActionOutcome::Applied
+    }
+}
+
+/// Whether `a` and `b` are two spellings of the path of the same file, differing only in
+/// case on a case-insensitive file system.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    let same_name = a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase();
+    if a == b || same_name.not() {
+        return same_name;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (std::fs::metadata(a), std::fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    // The file systems of Windows ignore case.
+    #[cfg(not(unix))]
+    b.exists()
+}
+
+/// Moves the file to `new_path`, claimed with [`Outputs::claim`], and returns where the
+/// file was before.
+pub async fn move_to(
+    ctx: &ActionContext,
+    entry: &mut FileEntry,
+    new_path: PathBuf,
+) -> anyhow::Result<PathBuf> {
+    if let Err(err) = ctx.io.rename(&entry.path, &new_path).await {
+        ctx.outputs.remove(&new_path);
+        return Err(err.into());
+    }
+
+    Ok(std::mem::replace(&mut entry.path, new_path))
+}
+
+/// A new version of a file written under a temporary name next to it, which then takes
+/// the place of the file with a rename so that it is never seen half-written.
+#[derive(Debug)]
+pub struct Replacement {
+    path: PathBuf,
+    temp: PathBuf,
+    written: u64,
+}
+
+impl Replacement {
+    /// Creates the empty `.{name}.tmp` next to `path`.
+    pub async fn create(ctx: &ActionContext, path: &Path) -> anyhow::Result<Self> {
+        let dir = path.parent().unwrap_or(&ctx.root);
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp = ctx.outputs.claim(dir, &format!(".{name}"), Some("tmp"));
+
+        let replacement = Self {
+            path: path.to_path_buf(),
+            temp,
+            written: 0,
+        };
+        if let Err(err) = ctx.io.write(&replacement.temp, Vec::new()).await {
+            replacement.discard(ctx).await;
+            return Err(err.into());
+        }
+
+        Ok(replacement)
+    }
+
+    /// Appends `chunk` to the new version.
+    pub async fn write(&mut self, ctx: &ActionContext, chunk: Vec<u8>) -> anyhow::Result<()> {
+        let len = chunk.len() as u64;
+        ctx.io.write_at(&self.temp, self.written, chunk).await?;
+        self.written += len;
+
+        Ok(())
+    }
+
+    /// Flushes the new version to disk, gives it the permissions of the file and moves it
+    /// over the file. It is removed if any of this fails.
+    pub async fn commit(self, ctx: &ActionContext) -> anyhow::Result<()> {
+        let result = async {
+            // Flushed first, read-only permissions would keep it from being opened.
+            ctx.io.fsync(&self.temp).await?;
+            let permissions = tokio::fs::metadata(&self.path).await?.permissions();
+            tokio::fs::set_permissions(&self.temp, permissions).await?;
+            ctx.io.rename(&self.temp, &self.path).await
+        }
+        .await;
+
+        match result {
+            Ok(_) => {
+                ctx.outputs.remove(&self.temp);
+                Ok(())
+            }
+            Err(err) => {
+                self.discard(ctx).await;
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Removes the new version, leaving the file as it was.
+    pub async fn discard(self, ctx: &ActionContext) {
+        let _ = ctx.io.remove(&self.temp).await;
+        ctx.outputs.remove(&self.temp);
+    }
+}
+
+/// Replaces the content of `path` with `content` through a [`Replacement`].
+pub async fn replace(ctx: &ActionContext, path: &Path, content: Vec<u8>) -> anyhow::Result<()> {
+    let mut replacement = Replacement::create(ctx, path).await?;
+
+    match replacement.write(ctx, content).await {
+        Ok(_) => replacement.commit(ctx).await,
+        Err(err) => {
+            replacement.discard(ctx).await;
+            Err(err)
+        }
+    }
+}
+
+/// Truncates the file to zero bytes.
+#[derive(Debug)]
+pub struct Truncate;
+
+#[async_trait]
+impl FileAction for Truncate {
+    fn label(&self) -> &str {
+        "Cleared"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        ctx.io.write(&entry.path, Vec::new()).await.into()
+    }
+}
+
+/// Overwrites the content with zeros, keeping the file size.
+#[derive(Debug)]
+pub struct Overwrite;
+
+#[async_trait]
+impl FileAction for Overwrite {
+    fn label(&self) -> &str {
+        "Overwritten"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        match overwrite(ctx, entry, Fill::Zeros).await {
+            Ok(0) => ActionOutcome::Skipped,
+            Ok(_) => ActionOutcome::Applied,
+            Err(err) => ActionOutcome::Failed(err),
+        }
+    }
+}
+
+/// Overwrites the whole file with `fill` and returns the number of bytes written.
+async fn overwrite(ctx: &ActionContext, entry: &FileEntry, fill: Fill) -> anyhow::Result<u64> {
+    let path = &entry.path;
+    let len = tokio::fs::metadata(path).await?.len();
+
+    if len > 0 {
+        ctx.io
+            .fill(path, 0, len, fill, &|position| entry.advance(position))
+            .await?;
+        ctx.io.fsync(path).await?;
+    }
+
+    Ok(len)
+}
+
+/// Replaces the content with a placeholder.
+#[derive(Debug, Clone)]
+pub struct Replace {
+    placeholder: Placeholder,
+}
+
+#[derive(Debug, Clone)]
+pub enum Placeholder {
+    /// A fixed text such as "REDACTED".
+    Text(String),
+    /// The content of a template file, read again for every file.
+    Template(PathBuf),
+    /// Random bytes, as many as the file had.
+    Random,
+}
+
+impl Replace {
+    pub fn new(placeholder: Placeholder) -> Self {
+        Self { placeholder }
+    }
+}
+
+#[async_trait]
+impl FileAction for Replace {
+    fn label(&self) -> &str {
+        "Replaced"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        let content = match &self.placeholder {
+            Placeholder::Text(text) => text.clone().into_bytes(),
+            Placeholder::Template(template) => match ctx.io.read(template).await {
+                Ok(content) => content,
+                Err(err) => {
+                    let err = anyhow::Error::new(err);
+                    return ActionOutcome::Failed(
+                        err.context(format!("reading {}", template.display())),
+                    );
+                }
+            },
+            Placeholder::Random => {
+                return overwrite(ctx, entry, Fill::Random).await.map(|_| ()).into()
+            }
+        };
+
+        ctx.io.write(&entry.path, content).await.into()
+    }
+}
+
+/// Removes the file.
+#[derive(Debug)]
+pub struct Delete;
+
+#[async_trait]
+impl FileAction for Delete {
+    fn label(&self) -> &str {
+        "Deleted"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        ctx.io.remove(&entry.path).await.into()
+    }
+}
+
+/// A text file that actions append lines to, one file at a time.
+#[derive(Debug)]
+pub struct Manifest {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl Manifest {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Default::default(),
+        }
+    }
+
+    pub async fn append(&self, content: &[u8]) -> anyhow::Result<()> {
+        let _guard = self.lock.lock().await;
+        append(&self.path, content).await
+    }
+}
+
+/// Appends the SHA-256 of the file to a manifest in `sha256sum` format.
+#[derive(Debug)]
+pub struct Hash {
+    manifest: Manifest,
+}
+
+impl Hash {
+    pub fn new(manifest: PathBuf) -> Self {
+        Self {
+            manifest: Manifest::new(manifest),
+        }
+    }
+}
+
+#[async_trait]
+impl FileAction for Hash {
+    fn label(&self) -> &str {
+        "Hashed"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        let digest = match sha256(ctx, entry).await {
+            Ok(digest) => digest,
+            Err(err) => return ActionOutcome::Failed(err),
+        };
+
+        let name = entry.path.strip_prefix(&ctx.root).unwrap_or(&entry.path);
+        let line = format!("{digest}  {}\n", name.display());
+
+        self.manifest.append(line.as_bytes()).await.into()
+    }
+}
+
+async fn sha256(ctx: &ActionContext, entry: &FileEntry) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut position = 0;
+
+    loop {
+        let chunk = ctx.io.read_at(&entry.path, position, CHUNK_SIZE).await?;
+        hasher.update(&chunk);
+
+        position += chunk.len() as u64;
+        entry.advance(position);
+        if chunk.len() < CHUNK_SIZE {
+            break;
+        }
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Appends `content` to `path`, creating the file and its directory if needed.
+async fn append(path: &Path, content: &[u8]) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| dir.as_os_str().is_empty().not()) {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("creating {}", dir.display()))?;
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .with_context(|| format!("opening {}", path.display()))?;
+    file.write_all(content).await?;
+    // Waits for the write to land, which tokio otherwise finishes in the background.
+    file.flush().await?;
+    Ok(())
+}
+
+/// Scrubs the file system metadata of the file: timestamps, extended attributes
+/// and ACLs, and permission bits.
+#[derive(Debug, Clone)]
+pub struct StripMetadata {
+    times: Timestamps,
+    xattrs: bool,
+    mode: Option<u32>,
+}
+
+/// What the access and modification times are set to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timestamps {
+    Fixed(SystemTime),
+    /// A different random time within the last year for every file.
+    Random,
+}
+
+impl Timestamps {
+    pub fn pick(&self) -> SystemTime {
+        match self {
+            Timestamps::Fixed(time) => *time,
+            Timestamps::Random => {
+                let year = Duration::from_secs(365 * 24 * 60 * 60);
+                SystemTime::now() - rand::thread_rng().gen_range(Duration::ZERO..year)
+            }
+        }
+    }
+}
+
+impl Default for Timestamps {
+    fn default() -> Self {
+        Timestamps::Fixed(SystemTime::UNIX_EPOCH)
+    }
+}
+
+impl Default for StripMetadata {
+    fn default() -> Self {
+        Self {
+            times: Default::default(),
+            xattrs: true,
+            mode: Some(0o644),
+        }
+    }
+}
+
+impl StripMetadata {
+    pub fn with_times(self, times: Timestamps) -> Self {
+        Self { times, ..self }
+    }
+
+    /// Removes every extended attribute, including POSIX ACLs, when `xattrs` is set.
+    pub fn with_xattrs(self, xattrs: bool) -> Self {
+        Self { xattrs, ..self }
+    }
+
+    /// Sets the permission bits to `mode`, or keeps them when `None`.
+    pub fn with_mode(self, mode: Option<u32>) -> Self {
+        Self { mode, ..self }
+    }
+
+    fn scrub(&self, path: &Path) -> anyhow::Result<()> {
+        if self.xattrs {
+            strip_xattrs(path)?;
+        }
+        if let Some(mode) = self.mode {
+            set_mode(path, mode)?;
+        }
+
+        let time = filetime::FileTime::from_system_time(self.times.pick());
+        filetime::set_file_times(path, time, time)?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FileAction for StripMetadata {
+    fn label(&self) -> &str {
+        "Stripped"
+    }
+
+    async fn apply(&self, _ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        let scrub = self.clone();
+        let path = entry.path.clone();
+        let result = tokio::task::spawn_blocking(move || scrub.scrub(&path)).await;
+
+        match result {
+            Ok(result) => result.into(),
+            Err(err) => ActionOutcome::Failed(err.into()),
+        }
+    }
+}
+
+/// Removes the extended attributes of `path`. ACLs are stored as `system.posix_acl_*`
+/// attributes and go with them; `security.*` labels belong to the system policy and
+/// are kept.
+#[cfg(unix)]
+fn strip_xattrs(path: &Path) -> anyhow::Result<()> {
+    for name in xattr::list(path)? {
+        if name.to_string_lossy().starts_with("security.") {
+            continue;
+        }
+
+        xattr::remove(path, &name)
+            .with_context(|| format!("removing {}", name.to_string_lossy()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn strip_xattrs(_path: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(path: &Path, mode: u32) -> anyhow::Result<()> {
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o200 == 0);
+
+    std::fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui::cleaner::io::TokioBackend;
+    use regex::Regex;
+
+    #[derive(Debug)]
+    struct Fixed(&'static str, fn() -> ActionOutcome);
+
+    #[async_trait]
+    impl FileAction for Fixed {
+        fn label(&self) -> &str {
+            self.0
+        }
+
+        async fn apply(&self, _ctx: &ActionContext, _entry: &mut FileEntry) -> ActionOutcome {
+            (self.1)()
+        }
+    }
+
+    fn context(root: &Path) -> ActionContext {
+        ActionContext {
+            root: root.to_path_buf(),
+            outputs: Default::default(),
+            io: Arc::new(TokioBackend),
+        }
+    }
+
+    #[tokio::test]
+    async fn pipeline_stops_at_the_first_failure() {
+        let root = tempfile::tempdir().unwrap();
+        let pipeline = Pipeline::new()
+            .then(Fixed("Applied", || ActionOutcome::Applied))
+            .then(Fixed("Skipped", || ActionOutcome::Skipped))
+            .then(Fixed("Failed", || {
+                ActionOutcome::Failed(anyhow::anyhow!("broken"))
+            }))
+            .then(Fixed("Unreached", || ActionOutcome::Applied));
+        let stopping = Pipeline::new()
+            .then(Fixed("Stopped", || ActionOutcome::Stop))
+            .then(Fixed("Unreached", || ActionOutcome::Applied));
+        let counters = Arc::new(Counters::new(pipeline.len()));
+        let mut entry = FileEntry::new(0, root.path().join("a"), 0, counters.clone());
+
+        let outcome = pipeline
+            .run(&context(root.path()), &mut entry, &counters)
+            .await;
+
+        match outcome {
+            FileOutcome::Failed { action, error } => {
+                assert_eq!(action, "Failed");
+                assert_eq!(error, "broken");
+            }
+            outcome => panic!("unexpected {outcome:?}"),
+        }
+        assert_eq!(counters.snapshot(), [1, 0, 0, 0]);
+
+        let counters = Arc::new(Counters::new(stopping.len()));
+        let outcome = stopping
+            .run(&context(root.path()), &mut entry, &counters)
+            .await;
+        assert!(matches!(outcome, FileOutcome::Processed));
+        assert_eq!(counters.snapshot(), [1, 0]);
+    }
+
+    #[test]
+    fn claims_skip_existing_and_claimed_paths() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("File0.txt"), "").unwrap();
+        let outputs = Outputs::default();
+
+        let first = outputs.claim(root.path(), "File0", Some("txt"));
+        let second = outputs.claim(root.path(), "File0", Some("txt"));
+        assert_eq!(first, root.path().join("File0(1).txt"));
+        assert_eq!(second, root.path().join("File0(2).txt"));
+
+        assert!(outputs.remove(&first));
+        assert_eq!(outputs.claim(root.path(), "File0", Some("txt")), first);
+        assert_eq!(
+            free_path(root.path(), "File0", None),
+            root.path().join("File0")
+        );
+    }
+
+    #[test]
+    fn concurrent_claims_get_distinct_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let outputs = Arc::new(Outputs::default());
+
+        let claims: HashSet<_> = (0..8)
+            .map(|_| {
+                let outputs = outputs.clone();
+                let dir = root.path().to_path_buf();
+                std::thread::spawn(move || {
+                    (0..16)
+                        .map(|_| outputs.claim(&dir, "File", Some("txt")))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .flat_map(|worker| worker.join().unwrap())
+            .collect();
+
+        assert_eq!(claims.len(), 8 * 16);
+    }
+
+    #[tokio::test]
+    async fn placeholders_replace_the_content() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        let path = root.path().join("secret");
+        let template = root.path().join("template");
+        std::fs::write(&template, "see the archive").unwrap();
+        let mut entry = FileEntry::new(0, path.clone(), 11, Arc::new(Counters::new(1)));
+
+        for (placeholder, expected) in [
+            (Placeholder::Text("REDACTED".to_string()), &b"REDACTED"[..]),
+            (Placeholder::Template(template), b"see the archive"),
+        ] {
+            std::fs::write(&path, "credentials").unwrap();
+            let outcome = Replace::new(placeholder).apply(&ctx, &mut entry).await;
+            assert!(matches!(outcome, ActionOutcome::Applied));
+            assert_eq!(std::fs::read(&path).unwrap(), expected);
+        }
+
+        std::fs::write(&path, "credentials").unwrap();
+        let outcome = Replace::new(Placeholder::Random)
+            .apply(&ctx, &mut entry)
+            .await;
+        assert!(matches!(outcome, ActionOutcome::Applied));
+        let random = std::fs::read(&path).unwrap();
+        assert_eq!(random.len(), 11);
+        assert_ne!(random, b"credentials");
+
+        let missing = Placeholder::Template(root.path().join("missing"));
+        let outcome = Replace::new(missing).apply(&ctx, &mut entry).await;
+        assert!(matches!(outcome, ActionOutcome::Failed(_)));
+    }
+
+    #[tokio::test]
+    async fn metadata_is_scrubbed() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("file");
+        std::fs::write(&path, "content").unwrap();
+        // Not every file system supports user attributes.
+        #[cfg(unix)]
+        let xattrs = xattr::set(&path, "user.origin", b"camera").is_ok();
+        let mut entry = FileEntry::new(0, path.clone(), 7, Arc::new(Counters::new(1)));
+
+        let at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let strip = StripMetadata::default()
+            .with_times(Timestamps::Fixed(at))
+            .with_mode(Some(0o600));
+        let outcome = strip.apply(&context(root.path()), &mut entry).await;
+
+        assert!(matches!(outcome, ActionOutcome::Applied));
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(metadata.modified().unwrap(), at);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(metadata.permissions().mode() & 0o7777, 0o600);
+            if xattrs {
+                assert_eq!(xattr::get(&path, "user.origin").unwrap(), None);
+            }
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), b"content");
+    }
+
+    #[test]
+    fn random_timestamps_fall_within_the_last_year() {
+        let year = Duration::from_secs(366 * 24 * 60 * 60);
+
+        for _ in 0..100 {
+            let time = Timestamps::Random.pick();
+            assert!(time <= SystemTime::now() && time >= SystemTime::now() - year);
+        }
+    }
+
+    #[tokio::test]
+    async fn pattern_renames_do_not_reuse_names_freed_during_the_job() {
+        let pattern =
+            RenamePattern::new().with_replacement(Regex::new("^(.)").unwrap(), "${1}2".to_string());
+        let rename = Rename::new(Naming::Pattern(Arc::new(pattern)));
+
+        // a.txt -> a2.txt while a2.txt -> a22.txt, processed in both orders.
+        for order in [["a2.txt", "a.txt"], ["a.txt", "a2.txt"]] {
+            let root = tempfile::tempdir().unwrap();
+            let ctx = context(root.path());
+            for name in order {
+                std::fs::write(root.path().join(name), name).unwrap();
+            }
+
+            for name in order {
+                let path = root.path().join(name);
+                let mut entry = FileEntry::new(0, path, 1, Arc::new(Counters::new(1)));
+                let outcome = rename.apply(&ctx, &mut entry).await;
+                assert!(matches!(outcome, ActionOutcome::Applied));
+            }
+
+            let read = |name: &str| std::fs::read_to_string(root.path().join(name)).unwrap();
+            assert_eq!(read("a2(1).txt"), "a.txt");
+            assert_eq!(read("a22.txt"), "a2.txt");
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn same_file_needs_the_same_name_but_for_case() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("Photo.JPG");
+        std::fs::write(&path, "").unwrap();
+        std::fs::write(root.path().join("photo.jpg"), "").unwrap();
+
+        assert!(same_file(&path, &path));
+        assert!(same_file(&path, &root.path().join("other.JPG")).not());
+        // Two files on a case-sensitive file system.
+        assert!(same_file(&path, &root.path().join("photo.jpg")).not());
+        assert!(same_file(&path, &root.path().join("PHOTO.JPG")).not());
+    }
+
+    #[tokio::test]
+    async fn pseudonyms_are_recorded_once_renamed() {
+        let root = tempfile::tempdir().unwrap();
+        let data = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        for dir in ["a", "b"] {
+            std::fs::create_dir(root.path().join(dir)).unwrap();
+            std::fs::write(root.path().join(dir).join("report.pdf"), dir).unwrap();
+        }
+        let mapping = data.path().join("MAPPING");
+        let rename = Rename::new(Naming::Pseudonym(Arc::new(Pseudonyms::new(
+            b"key",
+            mapping.clone(),
+        ))));
+
+        let mut renamed = Vec::new();
+        for dir in ["a", "b"] {
+            let path = root.path().join(dir).join("report.pdf");
+            let mut entry = FileEntry::new(0, path, 1, Arc::new(Counters::new(1)));
+            let outcome = rename.apply(&ctx, &mut entry).await;
+            assert!(matches!(outcome, ActionOutcome::Applied));
+            renamed.push(entry.path);
+        }
+
+        // The same name gets the same pseudonym in every folder.
+        let name = |path: &PathBuf| path.file_name().unwrap().to_string_lossy().into_owned();
+        assert_eq!(name(&renamed[0]), name(&renamed[1]));
+        assert!(name(&renamed[0]).ends_with(".pdf") && name(&renamed[0]).contains('(').not());
+        let renames = crate::gui::cleaner::pseudonym::reveal(b"key", &mapping).unwrap();
+        assert_eq!(renames.len(), 2);
+        assert_eq!(renames[0].from, Path::new("a/report.pdf"));
+        assert_eq!(root.path().join(&renames[0].to), renamed[0]);
+    }
+
+    #[tokio::test]
+    async fn files_are_moved_back_when_the_mapping_cannot_be_written() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        let path = root.path().join("report.pdf");
+        std::fs::write(&path, "content").unwrap();
+        // A directory cannot be appended to.
+        let mapping = root.path().join("MAPPING");
+        std::fs::create_dir(&mapping).unwrap();
+        let rename = Rename::new(Naming::Pseudonym(Arc::new(Pseudonyms::new(
+            b"key", mapping,
+        ))));
+
+        let mut entry = FileEntry::new(0, path.clone(), 7, Arc::new(Counters::new(1)));
+        let outcome = rename.apply(&ctx, &mut entry).await;
+
+        assert!(matches!(outcome, ActionOutcome::Failed(_)));
+        assert_eq!(entry.path, path);
+        assert_eq!(std::fs::read(&path).unwrap(), b"content");
+        assert_eq!(std::fs::read_dir(root.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn progress_counts_the_furthest_pass_once() {
+        let counters = Arc::new(Counters::new(0));
+        let entry = FileEntry::new(0, PathBuf::from("a"), 100, counters.clone());
+
+        entry.advance(60);
+        entry.advance(30);
+        entry.advance(500);
+        assert_eq!(counters.bytes(), 100);
+
+        counters.finish(&entry);
+        assert_eq!(counters.bytes(), 100);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::gui::cleaner::report::SkipReason;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a scan of the open files is reused before `/proc` is read again.
+const OPEN_FILES_TTL: Duration = Duration::from_secs(1);
+
+/// Number of times an unstable file is put back before it is skipped for good.
+pub const MAX_DEFERRALS: usize = 3;
+
+/// Tells apart files that are safe to process from files other programs are using.
+#[derive(Debug, Default)]
+pub struct BusyCheck {
+    open_files: Option<OpenFiles>,
+    stable_for: Option<Duration>,
+    defer: bool,
+}
+
+/// What to do with a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Ready,
+    /// Try the file again once the time has passed.
+    Defer(Duration),
+    Skip(SkipReason),
+}
+
+impl BusyCheck {
+    /// Checks that files are not open in another process when `skip_open` is set, and
+    /// that they were not modified within `stable_for`. Unstable files are retried later
+    /// in the job when `defer` is set, and skipped otherwise.
+    pub fn new(skip_open: bool, stable_for: Option<Duration>, defer: bool) -> Self {
+        Self {
+            open_files: skip_open.then(OpenFiles::default),
+            stable_for,
+            defer,
+        }
+    }
+
+    /// Decides the fate of a file that has already been put back `deferrals` times.
+    ///
+    /// May read `/proc`, so it is best called on a blocking thread.
+    pub fn check(&self, metadata: &Metadata, deferrals: usize) -> Verdict {
+        if let Some(stable_for) = self.stable_for {
+            let age = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .unwrap_or(stable_for);
+
+            if age < stable_for {
+                return match self.defer && deferrals < MAX_DEFERRALS {
+                    true => Verdict::Defer(stable_for - age),
+                    false => Verdict::Skip(SkipReason::Modified),
+                };
+            }
+        }
+
+        match &self.open_files {
+            Some(open_files) if open_files.contains(metadata) => Verdict::Skip(SkipReason::Open),
+            _ => Verdict::Ready,
+        }
+    }
+}
+
+/// Files opened by other processes, as listed in `/proc/*/fd`.
+///
+/// A scan is reused for [`OPEN_FILES_TTL`] so that large jobs do not read `/proc`
+/// for every file; a file opened in the meantime may go unnoticed.
+#[derive(Debug, Default)]
+struct OpenFiles {
+    scan: Mutex<Option<(Instant, HashSet<FileId>)>>,
+}
+
+/// Device and inode of a file, which stay the same whatever path it is opened by.
+type FileId = (u64, u64);
+
+impl OpenFiles {
+    fn contains(&self, metadata: &Metadata) -> bool {
+        let id = match file_id(metadata) {
+            Some(id) => id,
+            None => return false,
+        };
+
+        let mut scan = self.scan.lock();
+        match &*scan {
+            Some((at, ids)) if at.elapsed() < OPEN_FILES_TTL => ids.contains(&id),
+            _ => {
+                let ids = open_files();
+                let open = ids.contains(&id);
+
+                *scan = Some((Instant::now(), ids));
+                open
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<FileId> {
+    None
+}
+
+/// Identities of the regular files open in any process but this one.
+///
+/// Processes that cannot be inspected, usually those of other users when not running
+/// as root, are left out.
+#[cfg(target_os = "linux")]
+fn open_files() -> HashSet<FileId> {
+    let own = std::process::id().to_string();
+    let processes = match std::fs::read_dir("/proc") {
+        Ok(processes) => processes,
+        Err(_) => return HashSet::new(),
+    };
+
+    processes
+        .filter_map(Result::ok)
+        .filter(|process| {
+            let name = process.file_name();
+            let name = name.to_string_lossy();
+            name != own && name.bytes().all(|byte| byte.is_ascii_digit())
+        })
+        .filter_map(|process| std::fs::read_dir(process.path().join("fd")).ok())
+        .flat_map(|fds| fds.filter_map(Result::ok))
+        .filter_map(|fd| std::fs::metadata(fd.path()).ok())
+        .filter(|metadata| metadata.is_file())
+        .filter_map(|metadata| file_id(&metadata))
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_files() -> HashSet<FileId> {
+    HashSet::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recently_modified_files_are_deferred_then_skipped() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("file");
+        std::fs::write(&path, "content").unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+        let minute = Some(Duration::from_secs(60));
+
+        assert_eq!(BusyCheck::default().check(&metadata, 0), Verdict::Ready);
+        assert_eq!(
+            BusyCheck::new(false, minute, false).check(&metadata, 0),
+            Verdict::Skip(SkipReason::Modified)
+        );
+
+        let deferring = BusyCheck::new(false, minute, true);
+        assert!(matches!(
+            deferring.check(&metadata, 0),
+            Verdict::Defer(wait) if wait <= Duration::from_secs(60)
+        ));
+        assert_eq!(
+            deferring.check(&metadata, MAX_DEFERRALS),
+            Verdict::Skip(SkipReason::Modified)
+        );
+
+        let old = filetime::FileTime::from_unix_time(0, 0);
+        filetime::set_file_mtime(&path, old).unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(deferring.check(&metadata, 0), Verdict::Ready);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn files_open_in_other_processes_are_skipped() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("file");
+        std::fs::write(&path, "content").unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+
+        let mut holder = std::process::Command::new("sleep")
+            .arg("10")
+            .stdin(std::fs::File::open(&path).unwrap())
+            .spawn()
+            .unwrap();
+        let open = BusyCheck::new(true, None, false).check(&metadata, 0);
+        holder.kill().unwrap();
+        holder.wait().unwrap();
+
+        assert_eq!(open, Verdict::Skip(SkipReason::Open));
+        assert_eq!(
+            BusyCheck::new(true, None, false).check(&metadata, 0),
+            Verdict::Ready
+        );
+    }
+}
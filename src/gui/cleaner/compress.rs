@@ -0,0 +1,428 @@
+use crate::gui::cleaner::action::{ActionContext, ActionOutcome, FileAction, FileEntry};
+use crate::gui::cleaner::blocking;
+use anyhow::bail;
+use async_trait::async_trait;
+use flate2::write::GzEncoder;
+use std::io::Write;
+use std::ops::{Not, RangeInclusive};
+use std::path::{Path, PathBuf};
+use xz2::write::{XzDecoder, XzEncoder};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Replaces the file with a compressed copy, `{name}.zst` by default, for data that
+/// must be kept but takes up space.
+///
+/// The original is only removed once the archive has been read back and matched
+/// against it. The archive keeps the permissions and modification time of the file.
+#[derive(Debug, Clone, Copy)]
+pub struct Compress {
+    codec: Codec,
+    level: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Codec {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Codec::Gzip => "gz",
+            Codec::Zstd => "zst",
+            Codec::Xz => "xz",
+        }
+    }
+
+    /// Levels the codec accepts, from fastest to smallest.
+    pub fn levels(&self) -> RangeInclusive<u32> {
+        match self {
+            Codec::Gzip | Codec::Xz => 0..=9,
+            Codec::Zstd => 1..=22,
+        }
+    }
+
+    pub fn default_level(&self) -> u32 {
+        match self {
+            Codec::Gzip | Codec::Xz => 6,
+            Codec::Zstd => 3,
+        }
+    }
+
+    fn encoder(&self, level: u32) -> std::io::Result<Encoder> {
+        let encoder = match self {
+            Codec::Gzip => {
+                Encoder::Gzip(GzEncoder::new(Vec::new(), flate2::Compression::new(level)))
+            }
+            Codec::Zstd => Encoder::Zstd(zstd::Encoder::new(Vec::new(), level as i32)?),
+            Codec::Xz => Encoder::Xz(XzEncoder::new(Vec::new(), level)),
+        };
+
+        Ok(encoder)
+    }
+
+    fn decoder(&self) -> std::io::Result<Decoder> {
+        let decoder = match self {
+            Codec::Gzip => Decoder::Gzip(flate2::write::GzDecoder::new(Vec::new())),
+            Codec::Zstd => Decoder::Zstd(zstd::stream::write::Decoder::new(Vec::new())?),
+            Codec::Xz => Decoder::Xz(XzDecoder::new(Vec::new())),
+        };
+
+        Ok(decoder)
+    }
+}
+
+impl Compress {
+    pub fn new(codec: Codec) -> Self {
+        Self {
+            codec,
+            level: codec.default_level(),
+        }
+    }
+
+    /// Compresses at `level`, one of [`Codec::levels`].
+    pub fn with_level(self, level: u32) -> Self {
+        debug_assert!(self.codec.levels().contains(&level));
+
+        Self { level, ..self }
+    }
+
+    /// Compresses the file to `archive` and returns the size of the archive.
+    async fn write_archive(
+        &self,
+        ctx: &ActionContext,
+        entry: &FileEntry,
+        archive: &Path,
+    ) -> anyhow::Result<u64> {
+        let mut encoder = self.codec.encoder(self.level)?;
+        let mut position = 0;
+        let mut written = 0;
+        ctx.io.write(archive, Vec::new()).await?;
+
+        loop {
+            let chunk = ctx.io.read_at(&entry.path, position, CHUNK_SIZE).await?;
+            if chunk.is_empty() {
+                break;
+            }
+            position += chunk.len() as u64;
+
+            // Compression is CPU-bound, the runtime threads are left to the I/O.
+            let compressed;
+            (encoder, compressed) = blocking(move || {
+                let compressed = encoder.compress(&chunk);
+                (encoder, compressed)
+            })
+            .await;
+            let compressed = compressed?;
+            let len = compressed.len() as u64;
+            ctx.io.write_at(archive, written, compressed).await?;
+
+            written += len;
+            entry.advance(position);
+        }
+
+        let end = blocking(move || encoder.finish()).await?;
+        let len = end.len() as u64;
+        ctx.io.write_at(archive, written, end).await?;
+        ctx.io.fsync(archive).await?;
+
+        Ok(written + len)
+    }
+
+    /// Checks that `archive` decompresses to the content of the file.
+    async fn verify(
+        &self,
+        ctx: &ActionContext,
+        entry: &FileEntry,
+        archive: &Path,
+    ) -> anyhow::Result<()> {
+        let mut decoder = self.codec.decoder()?;
+        let mut position = 0;
+        let mut checked = 0;
+
+        loop {
+            let chunk = ctx.io.read_at(archive, position, CHUNK_SIZE).await?;
+            if chunk.is_empty() {
+                break;
+            }
+            position += chunk.len() as u64;
+
+            let decoded;
+            (decoder, decoded) = blocking(move || {
+                let decoded = decoder.decompress(&chunk);
+                (decoder, decoded)
+            })
+            .await;
+            checked = matches(ctx, &entry.path, checked, decoded?).await?;
+        }
+
+        let end = blocking(move || decoder.finish()).await?;
+        checked = matches(ctx, &entry.path, checked, end).await?;
+        if ctx
+            .io
+            .read_at(&entry.path, checked, 1)
+            .await?
+            .is_empty()
+            .not()
+        {
+            bail!("the archive does not match the file");
+        }
+
+        Ok(())
+    }
+
+    /// Compresses the file, checks the archive and removes the file. Returns the path
+    /// and size of the archive.
+    async fn compress(
+        &self,
+        ctx: &ActionContext,
+        entry: &FileEntry,
+    ) -> anyhow::Result<(PathBuf, u64)> {
+        let dir = entry.path.parent().unwrap_or(&ctx.root);
+        let name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+        let archive = ctx.outputs.claim(dir, &name, Some(self.codec.extension()));
+
+        match self.replace_with_archive(ctx, entry, &archive).await {
+            Ok(len) => Ok((archive, len)),
+            Err(err) => {
+                ctx.outputs.remove(&archive);
+                let _ = ctx.io.remove(&archive).await;
+                Err(err)
+            }
+        }
+    }
+
+    async fn replace_with_archive(
+        &self,
+        ctx: &ActionContext,
+        entry: &FileEntry,
+        archive: &Path,
+    ) -> anyhow::Result<u64> {
+        let metadata = tokio::fs::metadata(&entry.path).await?;
+        let len = self.write_archive(ctx, entry, archive).await?;
+        self.verify(ctx, entry, archive).await?;
+
+        tokio::fs::set_permissions(archive, metadata.permissions()).await?;
+        let modified = filetime::FileTime::from_last_modification_time(&metadata);
+        filetime::set_file_mtime(archive, modified)?;
+        ctx.io.remove(&entry.path).await?;
+
+        Ok(len)
+    }
+}
+
+#[async_trait]
+impl FileAction for Compress {
+    fn label(&self) -> &str {
+        "Compressed"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        // Already compressed by an earlier run.
+        let extension = entry.path.extension().unwrap_or_default();
+        if extension.eq_ignore_ascii_case(self.codec.extension()) {
+            return ActionOutcome::Skipped;
+        }
+
+        match self.compress(ctx, entry).await {
+            Ok((archive, len)) => {
+                entry.compressed(len);
+                entry.path = archive;
+                ActionOutcome::Applied
+            }
+            Err(err) => ActionOutcome::Failed(err),
+        }
+    }
+}
+
+/// A compressor writing to memory, emptied after every chunk.
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Zstd(zstd::Encoder<'static, Vec<u8>>),
+    Xz(XzEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    /// Compresses `data` and returns what the compressor produced so far.
+    fn compress(&mut self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let output = match self {
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                encoder.get_mut()
+            }
+            Encoder::Zstd(encoder) => {
+                encoder.write_all(data)?;
+                encoder.get_mut()
+            }
+            Encoder::Xz(encoder) => {
+                encoder.write_all(data)?;
+                encoder.get_mut()
+            }
+        };
+
+        Ok(std::mem::take(output))
+    }
+
+    /// The end of the compressed stream.
+    fn finish(self) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish(),
+            Encoder::Xz(encoder) => encoder.finish(),
+        }
+    }
+}
+
+/// A decompressor writing to memory, emptied after every chunk.
+enum Decoder {
+    Gzip(flate2::write::GzDecoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Decoder<'static, Vec<u8>>),
+    Xz(XzDecoder<Vec<u8>>),
+}
+
+impl Decoder {
+    /// Decompresses `data` and returns what the decompressor produced so far.
+    fn decompress(&mut self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let output = match self {
+            Decoder::Gzip(decoder) => {
+                decoder.write_all(data)?;
+                decoder.get_mut()
+            }
+            Decoder::Zstd(decoder) => {
+                decoder.write_all(data)?;
+                decoder.flush()?;
+                decoder.get_mut()
+            }
+            Decoder::Xz(decoder) => {
+                decoder.write_all(data)?;
+                decoder.get_mut()
+            }
+        };
+
+        Ok(std::mem::take(output))
+    }
+
+    /// The end of the decompressed data, failing if the stream is cut short.
+    fn finish(self) -> std::io::Result<Vec<u8>> {
+        match self {
+            Decoder::Gzip(decoder) => decoder.finish(),
+            Decoder::Zstd(mut decoder) => {
+                decoder.flush()?;
+                Ok(decoder.into_inner())
+            }
+            Decoder::Xz(mut decoder) => decoder.finish(),
+        }
+    }
+}
+
+/// Checks that `decoded` is the content of `original` from `offset` on, and returns the
+/// offset following it.
+async fn matches(
+    ctx: &ActionContext,
+    original: &Path,
+    offset: u64,
+    decoded: Vec<u8>,
+) -> anyhow::Result<u64> {
+    if decoded.is_empty() {
+        return Ok(offset);
+    }
+
+    if ctx.io.read_at(original, offset, decoded.len()).await? != decoded {
+        bail!("the archive does not match the file");
+    }
+    Ok(offset + decoded.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui::cleaner::action::Counters;
+    use crate::gui::cleaner::io::TokioBackend;
+    use std::io::Read;
+    use std::sync::Arc;
+
+    fn context(root: &Path) -> ActionContext {
+        ActionContext {
+            root: root.to_path_buf(),
+            outputs: Default::default(),
+            io: Arc::new(TokioBackend),
+        }
+    }
+
+    fn decompress(codec: Codec, archive: &Path) -> Vec<u8> {
+        let archive = std::fs::File::open(archive).unwrap();
+        let mut decoder: Box<dyn Read> = match codec {
+            Codec::Gzip => Box::new(flate2::read::GzDecoder::new(archive)),
+            Codec::Zstd => Box::new(zstd::Decoder::new(archive).unwrap()),
+            Codec::Xz => Box::new(xz2::read::XzDecoder::new(archive)),
+        };
+
+        let mut content = Vec::new();
+        decoder.read_to_end(&mut content).unwrap();
+        content
+    }
+
+    #[tokio::test]
+    async fn files_are_replaced_with_archives() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        let content: Vec<u8> = (0..CHUNK_SIZE * 3).map(|i| (i % 251) as u8).collect();
+
+        for codec in [Codec::Gzip, Codec::Zstd, Codec::Xz] {
+            let path = root.path().join("data.bin");
+            std::fs::write(&path, &content).unwrap();
+            let counters = Arc::new(Counters::new(1));
+            let mut entry = FileEntry::new(0, path.clone(), content.len() as u64, counters);
+
+            let outcome = Compress::new(codec).apply(&ctx, &mut entry).await;
+            assert!(matches!(outcome, ActionOutcome::Applied), "{codec:?}");
+            assert!(path.exists().not());
+            assert_eq!(
+                entry.path,
+                root.path().join(format!("data.bin.{}", codec.extension()))
+            );
+            assert_eq!(decompress(codec, &entry.path), content);
+
+            let outcome = Compress::new(codec).apply(&ctx, &mut entry).await;
+            assert!(matches!(outcome, ActionOutcome::Skipped));
+        }
+    }
+
+    #[tokio::test]
+    async fn archives_that_do_not_match_are_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        let path = root.path().join("data.txt");
+        std::fs::write(&path, "the content").unwrap();
+        let entry = FileEntry::new(0, path, 11, Arc::new(Counters::new(1)));
+
+        for codec in [Codec::Gzip, Codec::Zstd, Codec::Xz] {
+            let compress = Compress::new(codec);
+            let archive = root.path().join("archive");
+            compress
+                .write_archive(&ctx, &entry, &archive)
+                .await
+                .unwrap();
+            compress.verify(&ctx, &entry, &archive).await.unwrap();
+
+            // Cut short, then an archive of other content.
+            let full = std::fs::read(&archive).unwrap();
+            std::fs::write(&archive, &full[..full.len() - 4]).unwrap();
+            assert!(
+                compress.verify(&ctx, &entry, &archive).await.is_err(),
+                "{codec:?}"
+            );
+
+            std::fs::write(&entry.path, "the content!").unwrap();
+            std::fs::write(&archive, &full).unwrap();
+            assert!(
+                compress.verify(&ctx, &entry, &archive).await.is_err(),
+                "{codec:?}"
+            );
+            std::fs::write(&entry.path, "the content").unwrap();
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use crate::gui::cleaner::action::{free_path, Timestamps};
+use crate::gui::cleaner::report::DirectoryReport;
+use filetime::FileTime;
+use std::collections::HashMap;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+
+/// Renames the subdirectories of a job folder to `Dir{index}`, deepest first, once
+/// its files have been processed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DirectoryRenamer {
+    times: DirectoryTimes,
+}
+
+/// What happens to the times of directories whose entries were renamed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DirectoryTimes {
+    /// Restore the times the directories had before the renaming.
+    #[default]
+    Preserve,
+    /// Set the times of every directory, the job folder included.
+    Set(Timestamps),
+}
+
+impl DirectoryRenamer {
+    pub fn new(times: DirectoryTimes) -> Self {
+        Self { times }
+    }
+
+    /// Lists the directories below `root`, and their times when they are to be
+    /// preserved. Called before the files are processed, since renaming files changes
+    /// the times of their directories.
+    pub fn plan(&self, root: &Path) -> anyhow::Result<Renaming> {
+        let root = root.to_path_buf();
+        let directories = subdirectories(&root)?;
+
+        let mut times = HashMap::new();
+        if self.times == DirectoryTimes::Preserve {
+            for dir in directories.iter().chain([&root]) {
+                if let Ok(metadata) = std::fs::metadata(dir) {
+                    let accessed = FileTime::from_last_access_time(&metadata);
+                    let modified = FileTime::from_last_modification_time(&metadata);
+                    times.insert(dir.clone(), (accessed, modified));
+                }
+            }
+        }
+
+        Ok(Renaming {
+            root,
+            directories,
+            times,
+            renamer: *self,
+        })
+    }
+}
+
+/// Directories of a job folder waiting to be renamed, see [`DirectoryRenamer::plan`].
+#[derive(Debug)]
+pub struct Renaming {
+    root: PathBuf,
+    directories: Vec<PathBuf>,
+    /// Access and modification times of the directories when the job started.
+    times: HashMap<PathBuf, (FileTime, FileTime)>,
+    renamer: DirectoryRenamer,
+}
+
+impl Renaming {
+    /// Renames the directories and returns the renames that succeeded.
+    ///
+    /// Children are renamed before their parents so that the paths still to be renamed
+    /// stay valid. A directory that cannot be renamed keeps its name.
+    pub fn run(self) -> Vec<DirectoryReport> {
+        let Renaming {
+            root,
+            directories,
+            times,
+            renamer,
+        } = self;
+        let root = root.as_path();
+
+        let mut renames = Vec::new();
+        let mut by_depth = directories.iter().enumerate().collect::<Vec<_>>();
+        by_depth.sort_by_key(|(_, dir)| std::cmp::Reverse(dir.components().count()));
+
+        for (index, dir) in by_depth {
+            // Removed as empty after the job, see [`remove_empty`].
+            if dir.exists().not() {
+                continue;
+            }
+
+            let name = format!("Dir{index}");
+            if dir.file_name() == Some(name.as_ref()) {
+                continue;
+            }
+
+            let parent = dir.parent().unwrap_or(root);
+            let renamed = free_path(parent, &name, None);
+
+            if std::fs::rename(dir, &renamed).is_ok() {
+                renames.push(DirectoryReport {
+                    from: dir.clone(),
+                    to: renamed,
+                });
+            }
+        }
+
+        // Renames run deepest first, so the final location of a path is found by
+        // applying the renames of the directory itself, then of its ancestors.
+        let final_path = |path: &Path| {
+            renames.iter().fold(path.to_path_buf(), |path, rename| {
+                match path.strip_prefix(&rename.from) {
+                    Ok(rest) if rest.as_os_str().is_empty() => rename.to.clone(),
+                    Ok(rest) => rename.to.join(rest),
+                    Err(_) => path,
+                }
+            })
+        };
+
+        for original in directories.iter().map(PathBuf::as_path).chain([root]) {
+            let dir = final_path(original);
+            if dir.exists().not() {
+                continue;
+            }
+            // Times that cannot be set are left as they are.
+            let _ = match renamer.times {
+                DirectoryTimes::Preserve => match times.get(original) {
+                    Some((accessed, modified)) => {
+                        filetime::set_file_times(&dir, *accessed, *modified)
+                    }
+                    None => Ok(()),
+                },
+                DirectoryTimes::Set(times) => {
+                    let time = FileTime::from_system_time(times.pick());
+                    filetime::set_file_times(&dir, time, time)
+                }
+            };
+        }
+
+        // The report maps every original path to where it ended up, not just to the
+        // name it got while its parent still had the original one.
+        let destinations = renames
+            .iter()
+            .map(|rename| final_path(&rename.from))
+            .collect::<Vec<_>>();
+        for (rename, to) in renames.iter_mut().zip(destinations) {
+            rename.to = to;
+        }
+
+        renames
+    }
+}
+
+/// Removes the directories below `root` left empty, deepest first, and returns them.
+/// Directories that cannot be removed are left in place.
+pub fn remove_empty(root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+
+    // Children come after their parents, so going backwards empties a directory of its
+    // empty children before it is checked itself.
+    for dir in subdirectories(root)?.into_iter().rev() {
+        let empty = std::fs::read_dir(&dir).is_ok_and(|mut entries| entries.next().is_none());
+        if empty.not() {
+            continue;
+        }
+
+        if std::fs::remove_dir(&dir).is_ok() {
+            removed.push(dir);
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Every directory below `root`, parents before their children.
+/// Symbolic links to directories are not followed.
+fn subdirectories(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut directories = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let mut children = std::fs::read_dir(&dir)?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+        children.sort();
+
+        pending.extend(children.iter().rev().cloned());
+        directories.extend(children);
+    }
+
+    Ok(directories)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directories_are_renamed_deepest_first() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        std::fs::create_dir_all(root.join("photos/2019")).unwrap();
+        std::fs::create_dir_all(root.join("taxes")).unwrap();
+        std::fs::write(root.join("photos/2019/File0.txt"), "").unwrap();
+        let old = FileTime::from_unix_time(1_000_000, 0);
+        filetime::set_file_times(root.join("photos"), old, old).unwrap();
+
+        let renaming = DirectoryRenamer::default().plan(root).unwrap();
+        std::fs::write(root.join("photos/touched"), "").unwrap();
+        let mut renames: Vec<_> = renaming
+            .run()
+            .into_iter()
+            .map(|rename| (rename.from, rename.to))
+            .collect();
+        renames.sort();
+
+        assert_eq!(
+            renames,
+            [
+                (root.join("photos"), root.join("Dir0")),
+                (root.join("photos/2019"), root.join("Dir0/Dir2")),
+                (root.join("taxes"), root.join("Dir1")),
+            ]
+        );
+        assert!(root.join("Dir0/Dir2/File0.txt").exists());
+        let metadata = std::fs::metadata(root.join("Dir0")).unwrap();
+        assert_eq!(FileTime::from_last_modification_time(&metadata), old);
+    }
+
+    #[test]
+    fn taken_names_get_a_suffix() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        std::fs::create_dir_all(root.join("a")).unwrap();
+        std::fs::create_dir_all(root.join("b")).unwrap();
+        std::fs::write(root.join("Dir1"), "a file").unwrap();
+
+        let renaming = DirectoryRenamer::new(DirectoryTimes::Set(Timestamps::default()))
+            .plan(root)
+            .unwrap();
+        let renames = renaming.run();
+
+        assert_eq!(renames.len(), 2);
+        assert!(root.join("Dir0").is_dir());
+        assert!(root.join("Dir1(1)").is_dir());
+        let metadata = std::fs::metadata(root).unwrap();
+        assert_eq!(
+            FileTime::from_last_modification_time(&metadata),
+            FileTime::zero()
+        );
+    }
+
+    #[test]
+    fn only_empty_directories_are_removed() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        std::fs::create_dir_all(root.join("empty/nested")).unwrap();
+        std::fs::create_dir_all(root.join("full/nested")).unwrap();
+        std::fs::write(root.join("full/file"), "").unwrap();
+
+        let mut removed = remove_empty(root).unwrap();
+        removed.sort();
+
+        assert_eq!(
+            removed,
+            [
+                root.join("empty"),
+                root.join("empty/nested"),
+                root.join("full/nested")
+            ]
+        );
+        assert!(root.join("full/file").exists());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::gui::cleaner::action::{ActionContext, ActionOutcome, FileAction, FileEntry};
+use crate::gui::cleaner::blocking;
+use crate::gui::cleaner::redact::{read_text, Redact};
+use anyhow::Context;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Looks for the data [`Redact`] masks without changing anything, and records the
+/// files where some was found.
+#[derive(Debug, Clone)]
+pub struct Discover {
+    finder: Redact,
+    findings: Arc<Findings>,
+}
+
+/// The files a [`Discover`] action found sensitive data in, shared with whoever
+/// shows them.
+#[derive(Debug, Default)]
+pub struct Findings(Mutex<Vec<Finding>>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub path: PathBuf,
+    pub matches: usize,
+    /// Names of the detectors that matched, in alphabetical order.
+    pub categories: Vec<&'static str>,
+}
+
+impl Findings {
+    /// The findings so far, leaving none behind.
+    pub fn take(&self) -> Vec<Finding> {
+        std::mem::take(&mut *self.0.lock())
+    }
+}
+
+impl Discover {
+    /// Looks for what `finder` would redact, recording the files in `findings`.
+    pub fn new(finder: Redact, findings: Arc<Findings>) -> Self {
+        Self { finder, findings }
+    }
+}
+
+#[async_trait]
+impl FileAction for Discover {
+    fn label(&self) -> &str {
+        "Flagged"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        let content = read_text(ctx, &entry.path).await;
+        entry.advance(entry.len);
+        let content = match content {
+            Ok(Some(content)) => content,
+            Ok(None) => return ActionOutcome::Skipped,
+            Err(err) => return ActionOutcome::Failed(err.into()),
+        };
+
+        let finder = self.finder.clone();
+        let matches = blocking(move || finder.find(&content)).await;
+        let categories = matches
+            .iter()
+            .map(|found| found.category)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        entry
+            .details
+            .insert("matches".to_string(), matches.len().into());
+        entry
+            .details
+            .insert("categories".to_string(), categories.clone().into());
+        if matches.is_empty() {
+            return ActionOutcome::Skipped;
+        }
+
+        self.findings.0.lock().push(Finding {
+            path: entry.path.clone(),
+            matches: matches.len(),
+            categories,
+        });
+
+        ActionOutcome::Applied
+    }
+}
+
+/// Writes `findings` to `path` as CSV with a `path,matches,categories` header, the
+/// categories separated by semicolons.
+pub fn export_csv(findings: &[Finding], path: &Path) -> anyhow::Result<()> {
+    let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    writeln!(writer, "path,matches,categories")?;
+    for finding in findings {
+        writeln!(
+            writer,
+            "{},{},{}",
+            csv_field(&finding.path.to_string_lossy()),
+            finding.matches,
+            finding.categories.join(";")
+        )?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes `findings` to `path` as a JSON array.
+pub fn export_json(findings: &[Finding], path: &Path) -> anyhow::Result<()> {
+    let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), findings)?;
+
+    Ok(())
+}
+
+/// Quotes `value` if it contains a separator, a quote or a line break.
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui::cleaner::action::Counters;
+    use crate::gui::cleaner::io::TokioBackend;
+
+    fn context(root: &Path) -> ActionContext {
+        ActionContext {
+            root: root.to_path_buf(),
+            outputs: Default::default(),
+            io: Arc::new(TokioBackend),
+        }
+    }
+
+    fn finding(path: &str, categories: Vec<&'static str>) -> Finding {
+        Finding {
+            path: PathBuf::from(path),
+            matches: categories.len(),
+            categories,
+        }
+    }
+
+    #[tokio::test]
+    async fn text_files_with_matches_are_recorded_untouched() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        let findings = Arc::new(Findings::default());
+        let discover = Discover::new(Redact::default(), findings.clone());
+        // A NUL byte past the sniffed start does not make a file binary.
+        let mut long = vec![b'a'; 10_000];
+        long.extend_from_slice(b"\0 bob@example.org 4111 1111 1111 1111");
+        let files = [
+            ("long.txt", long),
+            ("clean.txt", b"nothing".to_vec()),
+            ("data.bin", b"\0bob@example.org".to_vec()),
+        ];
+
+        let mut outcomes = Vec::new();
+        for (name, content) in &files {
+            let path = root.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            let counters = Arc::new(Counters::new(1));
+            let mut entry = FileEntry::new(0, path, content.len() as u64, counters);
+            outcomes.push(discover.apply(&ctx, &mut entry).await);
+        }
+
+        assert!(matches!(outcomes[0], ActionOutcome::Applied));
+        assert!(matches!(outcomes[1], ActionOutcome::Skipped));
+        assert!(matches!(outcomes[2], ActionOutcome::Skipped));
+        let found = findings.take();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, root.path().join("long.txt"));
+        assert_eq!(found[0].matches, 2);
+        assert_eq!(found[0].categories, ["card", "email"]);
+        assert_eq!(
+            std::fs::read(root.path().join("long.txt")).unwrap(),
+            files[0].1
+        );
+        assert!(findings.take().is_empty());
+    }
+
+    #[test]
+    fn findings_are_exported_as_csv_and_json() {
+        let root = tempfile::tempdir().unwrap();
+        let findings = [
+            finding("plain.txt", vec!["email"]),
+            finding("a, \"b\".txt", vec!["card", "iban"]),
+        ];
+
+        let csv = root.path().join("findings.csv");
+        export_csv(&findings, &csv).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&csv).unwrap(),
+            "path,matches,categories\nplain.txt,1,email\n\"a, \"\"b\"\".txt\",2,card;iban\n"
+        );
+
+        let json = root.path().join("findings.json");
+        export_json(&findings, &json).unwrap();
+        let value: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&json).unwrap()).unwrap();
+        assert_eq!(value[1]["categories"], serde_json::json!(["card", "iban"]));
+
+        assert!(export_csv(&findings, &root.path().join("missing/findings.csv")).is_err());
+    }
+}
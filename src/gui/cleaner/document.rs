@@ -0,0 +1,865 @@
+use crate::gui::cleaner::action::{replace, ActionContext, ActionOutcome, FileAction, FileEntry};
+use crate::gui::cleaner::blocking;
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use regex::bytes::Regex;
+use std::collections::{BTreeSet, HashSet};
+use std::io::{Cursor, Read, Write};
+use std::ops::{Not, Range};
+use std::sync::OnceLock;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// Starts of zip archives and PDFs.
+const MAGIC: [&[u8]; 2] = [b"PK\x03\x04", b"%PDF-"];
+
+/// Properties of `docProps/app.xml` naming people or places, the statistics stay.
+const APP_PROPERTIES: [&str; 5] = [
+    "Company",
+    "Manager",
+    "Template",
+    "TotalTime",
+    "HyperlinkBase",
+];
+
+/// Removes the author, company, revision count, comments and custom properties of
+/// Office documents (DOCX, XLSX, PPTX), OpenDocument files and PDFs.
+///
+/// Only the metadata parts of the Office and OpenDocument archives are rewritten, the
+/// other parts are copied without being recompressed. PDFs are blanked in place, the
+/// document information dictionary emptied and the XMP streams filled with spaces, so
+/// no offset moves. Files of other formats and documents without metadata are skipped.
+/// The names of the fields removed go to the report as `removed`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripDocumentMetadata;
+
+/// A document without its metadata.
+#[derive(Debug)]
+pub struct Stripped {
+    pub content: Vec<u8>,
+    /// Names of the fields removed, `custom:{name}` for custom properties and `XMP`
+    /// for XMP packets.
+    pub removed: BTreeSet<String>,
+}
+
+#[async_trait]
+impl FileAction for StripDocumentMetadata {
+    fn label(&self) -> &str {
+        "Stripped"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        // Other files are told apart by their start, without reading them whole.
+        match ctx.io.read_at(&entry.path, 0, 5).await {
+            Ok(start) if MAGIC.iter().any(|magic| start.starts_with(magic)) => {}
+            Ok(_) => return ActionOutcome::Skipped,
+            Err(err) => return ActionOutcome::Failed(err.into()),
+        }
+        let content = match ctx.io.read(&entry.path).await {
+            Ok(content) => content,
+            Err(err) => return ActionOutcome::Failed(err.into()),
+        };
+
+        let stripped = match blocking(move || strip(&content)).await {
+            Ok(Some(stripped)) if stripped.removed.is_empty().not() => stripped,
+            Ok(_) => return ActionOutcome::Skipped,
+            Err(err) => return ActionOutcome::Failed(err),
+        };
+        let removed = stripped.removed.into_iter().collect::<Vec<_>>();
+        entry.details.insert("removed".to_string(), removed.into());
+
+        if let Err(err) = replace(ctx, &entry.path, stripped.content).await {
+            return ActionOutcome::Failed(err);
+        }
+        entry.advance(entry.len);
+
+        ActionOutcome::Applied
+    }
+}
+
+/// `content` without its metadata, or `None` if it is not a document of a supported
+/// format.
+pub fn strip(content: &[u8]) -> anyhow::Result<Option<Stripped>> {
+    let mut removed = BTreeSet::new();
+
+    let content = if content.starts_with(MAGIC[0]) {
+        let mut archive = ZipArchive::new(Cursor::new(content))?;
+        let package = match Package::detect(&mut archive)? {
+            Some(package) => package,
+            None => return Ok(None),
+        };
+        strip_package(archive, package, &mut removed).context("rewriting the archive")?
+    } else if content.starts_with(MAGIC[1]) {
+        strip_pdf(content, &mut removed).context("reading the PDF objects")?
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(Stripped { content, removed }))
+}
+
+/// Kinds of zip-based documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Package {
+    /// Office Open XML, the metadata in `docProps/`.
+    Office,
+    /// OpenDocument, the metadata in `meta.xml`.
+    OpenDocument,
+}
+
+impl Package {
+    fn detect(archive: &mut ZipArchive<Cursor<&[u8]>>) -> anyhow::Result<Option<Package>> {
+        if archive.by_name("[Content_Types].xml").is_ok() {
+            return Ok(Some(Package::Office));
+        }
+
+        let mut mimetype = String::new();
+        if let Ok(file) = archive.by_name("mimetype") {
+            file.take(128).read_to_string(&mut mimetype)?;
+        }
+        if mimetype.starts_with("application/vnd.oasis.opendocument") {
+            return Ok(Some(Package::OpenDocument));
+        }
+
+        Ok(None)
+    }
+
+    fn holds_metadata(&self, name: &str) -> bool {
+        matches!(
+            (self, name),
+            (
+                Package::Office,
+                "docProps/core.xml" | "docProps/app.xml" | "docProps/custom.xml"
+            ) | (Package::OpenDocument, "meta.xml")
+        )
+    }
+
+    /// `xml`, the part named `name`, without its metadata, or `None` if there is
+    /// nothing to remove.
+    fn strip_part(
+        &self,
+        name: &str,
+        xml: &str,
+        removed: &mut BTreeSet<String>,
+    ) -> anyhow::Result<Option<String>> {
+        let root = match children(xml, 0..xml.len())?.into_iter().next() {
+            Some(root) => root,
+            None => bail!("{name} has no root element"),
+        };
+
+        let dropped = match (self, name) {
+            (Package::Office, "docProps/core.xml") => children(xml, root.inner)?,
+            (Package::Office, "docProps/app.xml") => children(xml, root.inner)?
+                .into_iter()
+                .filter(|element| APP_PROPERTIES.contains(&element.local_name()))
+                .collect(),
+            (Package::Office, "docProps/custom.xml") => children(xml, root.inner)?,
+            (Package::OpenDocument, "meta.xml") => {
+                let meta = children(xml, root.inner)?
+                    .into_iter()
+                    .find(|element| element.local_name() == "meta");
+                match meta {
+                    Some(meta) => children(xml, meta.inner)?
+                        .into_iter()
+                        .filter(|element| element.local_name() != "document-statistic")
+                        .collect(),
+                    None => Vec::new(),
+                }
+            }
+            _ => return Ok(None),
+        };
+        if dropped.is_empty() {
+            return Ok(None);
+        }
+
+        let mut stripped = String::with_capacity(xml.len());
+        let mut end = 0;
+        for element in &dropped {
+            stripped.push_str(&xml[end..element.range.start]);
+            end = element.range.end;
+
+            // Custom properties are reported by their own name.
+            match element.local_name() {
+                "property" | "user-defined" => {
+                    let name = element.attribute("name").unwrap_or_default();
+                    removed.insert(format!("custom:{name}"));
+                }
+                local_name => {
+                    removed.insert(local_name.to_string());
+                }
+            }
+        }
+        stripped.push_str(&xml[end..]);
+
+        Ok(Some(stripped))
+    }
+}
+
+/// Copies the parts of the archive, the metadata ones without their metadata and the
+/// others as they are.
+fn strip_package(
+    mut archive: ZipArchive<Cursor<&[u8]>>,
+    package: Package,
+    removed: &mut BTreeSet<String>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let name = file.name().to_string();
+
+        // A part read through cannot be copied raw any more, it is written back.
+        let stripped = match package.holds_metadata(&name) {
+            true => {
+                let mut xml = String::new();
+                file.read_to_string(&mut xml)
+                    .with_context(|| format!("reading {name}"))?;
+                Some(package.strip_part(&name, &xml, removed)?.unwrap_or(xml))
+            }
+            false => None,
+        };
+
+        match stripped {
+            Some(xml) => {
+                let mut options = FileOptions::default()
+                    .compression_method(file.compression())
+                    .last_modified_time(file.last_modified());
+                if let Some(mode) = file.unix_mode() {
+                    options = options.unix_permissions(mode);
+                }
+                writer.start_file(name, options)?;
+                writer.write_all(xml.as_bytes())?;
+            }
+            None => writer.raw_copy_file(file)?,
+        }
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
+
+/// An element of an XML document, found by [`children`].
+#[derive(Debug)]
+struct Element<'a> {
+    name: &'a str,
+    start_tag: &'a str,
+    /// From the start of the start tag to the end of the end tag.
+    range: Range<usize>,
+    /// Between the tags, empty for empty-element tags.
+    inner: Range<usize>,
+}
+
+impl<'a> Element<'a> {
+    /// The name without its namespace prefix.
+    fn local_name(&self) -> &'a str {
+        self.name.rsplit(':').next().unwrap_or(self.name)
+    }
+
+    /// The value of the attribute whose local name is `name`.
+    fn attribute(&self, name: &str) -> Option<&'a str> {
+        static ATTRIBUTE: OnceLock<regex::Regex> = OnceLock::new();
+        let attribute = ATTRIBUTE.get_or_init(|| {
+            regex::Regex::new(r#"\s(?:[\w.-]+:)?([\w.-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#)
+                .expect("the attribute pattern is valid")
+        });
+
+        attribute
+            .captures_iter(self.start_tag)
+            .find(|captures| &captures[1] == name)
+            .and_then(|captures| captures.get(2).or_else(|| captures.get(3)))
+            .map(|value| value.as_str())
+    }
+}
+
+/// The elements directly within `range` of `xml`, in order. Comments, processing
+/// instructions and CDATA sections are stepped over.
+fn children(xml: &str, range: Range<usize>) -> anyhow::Result<Vec<Element<'_>>> {
+    let bytes = xml.as_bytes();
+    let find = |from: usize, needle: &str| {
+        xml[from..range.end]
+            .find(needle)
+            .map(|at| from + at + needle.len())
+            .with_context(|| format!("unterminated markup at byte {from}"))
+    };
+
+    let mut elements = Vec::new();
+    let mut open = None;
+    let mut depth = 0usize;
+    let mut position = range.start;
+
+    while let Some(at) = xml[position..range.end].find('<') {
+        let start = position + at;
+        let rest = &xml[start..range.end];
+
+        position = if rest.starts_with("<!--") {
+            find(start, "-->")?
+        } else if rest.starts_with("<![CDATA[") {
+            find(start, "]]>")?
+        } else if rest.starts_with("<?") {
+            find(start, "?>")?
+        } else if rest.starts_with("<!") {
+            find(start, ">")?
+        } else if rest.starts_with("</") {
+            let end = find(start, ">")?;
+            depth = match depth.checked_sub(1) {
+                Some(depth) => depth,
+                None => bail!("unexpected end tag at byte {start}"),
+            };
+            if let Some((name, start_tag, range_start, inner_start)) = open.take_if(|_| depth == 0)
+            {
+                elements.push(Element {
+                    name,
+                    start_tag,
+                    range: range_start..end,
+                    inner: inner_start..start,
+                });
+            }
+            end
+        } else {
+            // Attribute values may hold a `>`.
+            let mut quote = None;
+            let mut end = None;
+            for (offset, byte) in bytes[start..range.end].iter().enumerate() {
+                match (quote, byte) {
+                    (None, b'"' | b'\'') => quote = Some(*byte),
+                    (Some(open), _) if open == *byte => quote = None,
+                    (None, b'>') => {
+                        end = Some(start + offset + 1);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            let end = end.with_context(|| format!("unterminated tag at byte {start}"))?;
+
+            let start_tag = &xml[start..end];
+            let name = start_tag[1..]
+                .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                .next()
+                .unwrap_or_default();
+            let empty = start_tag.ends_with("/>");
+
+            match (depth, empty) {
+                (0, true) => elements.push(Element {
+                    name,
+                    start_tag,
+                    range: start..end,
+                    inner: end..end,
+                }),
+                (0, false) => open = Some((name, start_tag, start, end)),
+                _ => {}
+            }
+            if empty.not() {
+                depth += 1;
+            }
+            end
+        };
+    }
+    if depth != 0 {
+        bail!("unclosed element");
+    }
+
+    Ok(elements)
+}
+
+/// Empties the document information dictionaries and blanks the XMP streams and the
+/// references to them, leaving every byte where it was.
+fn strip_pdf(content: &[u8], removed: &mut BTreeSet<String>) -> anyhow::Result<Vec<u8>> {
+    static INFO: OnceLock<Regex> = OnceLock::new();
+    static OBJECT: OnceLock<Regex> = OnceLock::new();
+    static METADATA: OnceLock<Regex> = OnceLock::new();
+    let info = INFO.get_or_init(|| Regex::new(r"/Info\s+(\d+)\s+(\d+)\s+R").expect("valid"));
+    let object = OBJECT.get_or_init(|| Regex::new(r"\b(\d+)\s+(\d+)\s+obj\b").expect("valid"));
+    let metadata =
+        METADATA.get_or_init(|| Regex::new(r"/Metadata\s+(\d+)\s+(\d+)\s+R").expect("valid"));
+
+    let mut output = content.to_vec();
+    let info_objects = info
+        .captures_iter(content)
+        .map(|captures| (captures[1].to_vec(), captures[2].to_vec()))
+        .collect::<HashSet<_>>();
+    let mut info_found = HashSet::new();
+    let mut xmp_objects = HashSet::new();
+
+    for captures in object.captures_iter(content) {
+        let id = (captures[1].to_vec(), captures[2].to_vec());
+        let start = skip_whitespace(content, captures.get(0).map_or(0, |found| found.end()));
+        if content[start..].starts_with(b"<<").not() {
+            continue;
+        }
+        // Streams may hold anything looking like an object, those do not parse.
+        let (entries, end) = match dictionary(content, start, 0) {
+            Ok(dictionary) => dictionary,
+            Err(_) => continue,
+        };
+
+        if info_objects.contains(&id) {
+            for (key, _) in &entries {
+                removed.insert(String::from_utf8_lossy(&content[key.clone()]).into_owned());
+            }
+            output[start + 2..end - 2].fill(b' ');
+            info_found.insert(id);
+            continue;
+        }
+
+        let is_xmp = entries.iter().any(|(key, value)| {
+            &content[key.clone()] == b"Type" && &content[value.clone()] == b"/Metadata"
+        });
+        if is_xmp {
+            let data = stream_data(content, end)?;
+            if content[data.clone()]
+                .iter()
+                .all(u8::is_ascii_whitespace)
+                .not()
+            {
+                output[data].fill(b' ');
+                removed.insert("XMP".to_string());
+            }
+            xmp_objects.insert(id);
+        }
+    }
+
+    if info_objects.iter().any(|id| info_found.contains(id).not()) {
+        bail!("the document information is in a compressed object stream");
+    }
+    for captures in metadata.captures_iter(content) {
+        let id = (captures[1].to_vec(), captures[2].to_vec());
+        if xmp_objects.contains(&id) {
+            if let Some(reference) = captures.get(0) {
+                output[reference.range()].fill(b' ');
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+fn is_pdf_whitespace(byte: u8) -> bool {
+    matches!(byte, b'\0' | b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+fn is_pdf_regular(byte: u8) -> bool {
+    is_pdf_whitespace(byte).not() && b"()<>[]{}/%".contains(&byte).not()
+}
+
+/// Position of the first byte from `position` on that is neither whitespace nor part
+/// of a comment.
+fn skip_whitespace(content: &[u8], mut position: usize) -> usize {
+    while let Some(byte) = content.get(position) {
+        match byte {
+            b'%' => {
+                while content
+                    .get(position)
+                    .is_some_and(|byte| b"\r\n".contains(byte).not())
+                {
+                    position += 1;
+                }
+            }
+            byte if is_pdf_whitespace(*byte) => position += 1,
+            _ => break,
+        }
+    }
+
+    position
+}
+
+/// Dictionaries and arrays nested deeper than this are taken for a corrupt file.
+const MAX_NESTING: usize = 64;
+
+/// The keys, without their slash, and the values of the dictionary at `start`, and
+/// the position after it. `depth` counts the dictionaries and arrays it is in.
+fn dictionary(content: &[u8], start: usize, depth: usize) -> anyhow::Result<(Vec<Entry>, usize)> {
+    if depth == MAX_NESTING {
+        bail!("values nested too deep at byte {start}");
+    }
+    let mut entries = Vec::new();
+    let mut position = start + 2;
+
+    loop {
+        position = skip_whitespace(content, position);
+        match content.get(position..position + 2) {
+            Some(b">>") => return Ok((entries, position + 2)),
+            Some([b'/', _]) => {}
+            _ => bail!("expected a name at byte {position}"),
+        }
+
+        let key_end = skip_value(content, position, depth + 1)?;
+        let value_start = skip_whitespace(content, key_end);
+        let value_end = skip_value(content, value_start, depth + 1)?;
+        entries.push((position + 1..key_end, value_start..value_end));
+        position = value_end;
+    }
+}
+
+/// Ranges of the key and the value of a dictionary entry.
+type Entry = (Range<usize>, Range<usize>);
+
+/// Position after the value at `start`, an indirect reference counting as one value.
+/// `depth` counts the dictionaries and arrays the value is in.
+fn skip_value(content: &[u8], start: usize, depth: usize) -> anyhow::Result<usize> {
+    let rest = &content[start..];
+
+    let end = if rest.starts_with(b"<<") {
+        dictionary(content, start, depth)?.1
+    } else if rest.starts_with(b"<") {
+        match rest.iter().position(|byte| *byte == b'>') {
+            Some(at) => start + at + 1,
+            None => bail!("unterminated string at byte {start}"),
+        }
+    } else if rest.starts_with(b"(") {
+        let mut depth = 0;
+        let mut position = start;
+        loop {
+            match content.get(position) {
+                Some(b'\\') => position += 1,
+                Some(b'(') => depth += 1,
+                Some(b')') if depth == 1 => break position + 1,
+                Some(b')') => depth -= 1,
+                Some(_) => {}
+                None => bail!("unterminated string at byte {start}"),
+            }
+            position += 1;
+        }
+    } else if rest.starts_with(b"[") {
+        if depth == MAX_NESTING {
+            bail!("values nested too deep at byte {start}");
+        }
+        let mut position = start + 1;
+        loop {
+            position = skip_whitespace(content, position);
+            match content.get(position) {
+                Some(b']') => break position + 1,
+                Some(_) => position = skip_value(content, position, depth + 1)?,
+                None => bail!("unterminated array at byte {start}"),
+            }
+        }
+    } else {
+        let token_end = |from: usize| {
+            from + content[from..]
+                .iter()
+                .take_while(|byte| is_pdf_regular(**byte))
+                .count()
+        };
+        let end = match rest.starts_with(b"/") {
+            true => token_end(start + 1),
+            false => token_end(start),
+        };
+        if end == start {
+            bail!("unexpected byte at {start}");
+        }
+
+        // `12 0 R` is one value.
+        let is_integer = |range: Range<usize>| {
+            range.is_empty().not() && content[range].iter().all(u8::is_ascii_digit)
+        };
+        let generation = skip_whitespace(content, end);
+        let generation_end = token_end(generation);
+        let r = skip_whitespace(content, generation_end);
+        match is_integer(start..end)
+            && is_integer(generation..generation_end)
+            && token_end(r) == r + 1
+            && content[r] == b'R'
+        {
+            true => r + 1,
+            false => end,
+        }
+    };
+
+    Ok(end)
+}
+
+/// The data of the stream whose dictionary ends at `end`, up to `endstream`.
+fn stream_data(content: &[u8], end: usize) -> anyhow::Result<Range<usize>> {
+    let keyword = skip_whitespace(content, end);
+    if content[keyword..].starts_with(b"stream").not() {
+        bail!("expected a stream at byte {keyword}");
+    }
+
+    let mut start = keyword + b"stream".len();
+    if content[start..].starts_with(b"\r") {
+        start += 1;
+    }
+    if content[start..].starts_with(b"\n") {
+        start += 1;
+    }
+
+    let mut end = match content[start..]
+        .windows(b"endstream".len())
+        .position(|window| window == b"endstream")
+    {
+        Some(at) => start + at,
+        None => bail!("unterminated stream at byte {start}"),
+    };
+    while end > start && b"\r\n".contains(&content[end - 1]) {
+        end -= 1;
+    }
+
+    Ok(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui::cleaner::action::Counters;
+    use crate::gui::cleaner::io::TokioBackend;
+    use std::path::Path;
+    use std::sync::Arc;
+    use zip::CompressionMethod;
+
+    const PAGE: &str = "BT /F1 12 Tf (Author: /Info) Tj ET";
+
+    /// A PDF with an information dictionary, an XMP stream and a page, and an xref
+    /// table pointing at each object.
+    fn pdf() -> Vec<u8> {
+        let objects = [
+            "<< /Type /Catalog /Pages 5 0 R /Metadata 3 0 R >>".to_string(),
+            "<< /Author (Jane \\(JD\\)) /Producer <4142> /Keywords [(a) [(b)]] >>".to_string(),
+            "<< /Type /Metadata /Subtype /XML /Length 12 >>\nstream\n<x:xmpmeta/>\nendstream"
+                .to_string(),
+            format!("<< /Length {} >>\nstream\n{PAGE}\nendstream", PAGE.len()),
+            "<< /Type /Pages /Kids [] /Count 0 >>".to_string(),
+        ];
+
+        let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n{object}\nendobj\n", index + 1).as_bytes());
+        }
+
+        let xref = pdf.len();
+        pdf.extend_from_slice(
+            format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1).as_bytes(),
+        );
+        for offset in offsets {
+            pdf.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+        }
+        pdf.extend_from_slice(
+            format!("trailer\n<< /Size 6 /Root 1 0 R /Info 2 0 R >>\nstartxref\n{xref}\n%%EOF\n")
+                .as_bytes(),
+        );
+        pdf
+    }
+
+    fn zip(files: &[(&str, &str, CompressionMethod)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content, method) in files {
+            let options = FileOptions::default().compression_method(*method);
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn docx() -> Vec<u8> {
+        zip(&[
+            (
+                "[Content_Types].xml",
+                "<Types/>",
+                CompressionMethod::Deflated,
+            ),
+            (
+                "docProps/core.xml",
+                "<?xml version=\"1.0\"?>\n<cp:coreProperties xmlns:cp=\"c\" xmlns:dc=\"d\">\
+                 <dc:creator>Jane</dc:creator><!-- <x> --><cp:revision>3</cp:revision>\
+                 </cp:coreProperties>",
+                CompressionMethod::Deflated,
+            ),
+            (
+                "docProps/app.xml",
+                "<Properties><Company>Acme</Company><Pages>2</Pages></Properties>",
+                CompressionMethod::Stored,
+            ),
+            (
+                "docProps/custom.xml",
+                "<Properties><property fmtid='x' pid=\"2\" name=\"Client\">\
+                 <vt:lpwstr>Bob &gt; Al</vt:lpwstr></property></Properties>",
+                CompressionMethod::Deflated,
+            ),
+            (
+                "word/document.xml",
+                "<w:document><w:t>Hello Jane</w:t></w:document>",
+                CompressionMethod::Deflated,
+            ),
+        ])
+    }
+
+    fn read_part(content: &[u8], name: &str) -> String {
+        let mut archive = ZipArchive::new(Cursor::new(content)).unwrap();
+        let mut part = String::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_string(&mut part)
+            .unwrap();
+        part
+    }
+
+    fn context(root: &Path) -> ActionContext {
+        ActionContext {
+            root: root.to_path_buf(),
+            outputs: Default::default(),
+            io: Arc::new(TokioBackend),
+        }
+    }
+
+    #[test]
+    fn office_metadata_parts_are_emptied() {
+        let original = docx();
+        let stripped = strip(&original).unwrap().unwrap();
+
+        assert_eq!(
+            stripped.removed,
+            BTreeSet::from(["Company", "creator", "custom:Client", "revision"].map(String::from))
+        );
+        assert_eq!(
+            read_part(&stripped.content, "docProps/core.xml"),
+            "<?xml version=\"1.0\"?>\n<cp:coreProperties xmlns:cp=\"c\" xmlns:dc=\"d\">\
+             <!-- <x> --></cp:coreProperties>"
+        );
+        assert_eq!(
+            read_part(&stripped.content, "docProps/app.xml"),
+            "<Properties><Pages>2</Pages></Properties>"
+        );
+
+        // The other parts are copied without being recompressed.
+        let mut before = ZipArchive::new(Cursor::new(original.as_slice())).unwrap();
+        let mut after = ZipArchive::new(Cursor::new(stripped.content.as_slice())).unwrap();
+        let before = before.by_name("word/document.xml").unwrap();
+        let after = after.by_name("word/document.xml").unwrap();
+        assert_eq!(after.compression(), CompressionMethod::Deflated);
+        assert_eq!(after.compressed_size(), before.compressed_size());
+        assert_eq!(after.crc32(), before.crc32());
+
+        assert!(strip(&stripped.content)
+            .unwrap()
+            .unwrap()
+            .removed
+            .is_empty());
+    }
+
+    #[test]
+    fn opendocument_metadata_keeps_the_statistics() {
+        let odt = zip(&[
+            (
+                "mimetype",
+                "application/vnd.oasis.opendocument.text",
+                CompressionMethod::Stored,
+            ),
+            (
+                "meta.xml",
+                "<office:document-meta><office:meta>\
+                 <meta:initial-creator>Jane</meta:initial-creator>\
+                 <meta:user-defined meta:name=\"Client\">Bob</meta:user-defined>\
+                 <meta:document-statistic meta:page-count=\"1\"/>\
+                 </office:meta></office:document-meta>",
+                CompressionMethod::Deflated,
+            ),
+        ]);
+
+        let stripped = strip(&odt).unwrap().unwrap();
+        assert_eq!(
+            stripped.removed,
+            BTreeSet::from(["custom:Client", "initial-creator"].map(String::from))
+        );
+        assert_eq!(
+            read_part(&stripped.content, "meta.xml"),
+            "<office:document-meta><office:meta>\
+             <meta:document-statistic meta:page-count=\"1\"/>\
+             </office:meta></office:document-meta>"
+        );
+
+        let other = zip(&[("data.txt", "text", CompressionMethod::Stored)]);
+        assert!(strip(&other).unwrap().is_none());
+    }
+
+    #[test]
+    fn pdf_metadata_is_blanked_in_place() {
+        let original = pdf();
+        let stripped = strip(&original).unwrap().unwrap();
+        let content = stripped.content;
+
+        assert_eq!(
+            stripped.removed,
+            BTreeSet::from(["Author", "Keywords", "Producer", "XMP"].map(String::from))
+        );
+        assert_eq!(content.len(), original.len());
+
+        // Every object is still where the xref table says.
+        let text = String::from_utf8_lossy(&content);
+        let xref = text.find("xref\n").unwrap();
+        let entries = text[xref..].lines().skip(3).take(5);
+        for (index, entry) in entries.enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            let header = format!("{} 0 obj", index + 1);
+            assert!(content[offset..].starts_with(header.as_bytes()), "{entry}");
+        }
+
+        assert!(text.contains(PAGE));
+        assert!(text.contains("2 0 obj\n<<") && text.contains("Jane").not());
+        assert!(text.contains("<x:xmpmeta/>").not() && text.contains("/Metadata 3 0 R").not());
+        assert!(text.contains("/Info 2 0 R"));
+    }
+
+    #[test]
+    fn attributes_are_found_by_local_name() {
+        let xml = r#"<meta:user-defined meta:value-type='float' meta:name="Client"/>"#;
+        let element = children(xml, 0..xml.len()).unwrap().remove(0);
+
+        assert_eq!(element.local_name(), "user-defined");
+        assert_eq!(element.attribute("name"), Some("Client"));
+        assert_eq!(element.attribute("value-type"), Some("float"));
+        assert_eq!(element.attribute("type"), None);
+    }
+
+    #[test]
+    fn malformed_documents_fail_without_panicking() {
+        let pdf = pdf();
+        for len in 0..pdf.len() {
+            let _ = strip(&pdf[..len]);
+        }
+        let docx = docx();
+        for len in (0..docx.len()).step_by(7) {
+            let _ = strip(&docx[..len]);
+        }
+
+        let mut nested = b"%PDF-1.4\n1 0 obj\n".to_vec();
+        nested.extend(b"<< /A ".repeat(100_000));
+        nested.extend(b"[".repeat(100_000));
+        assert!(skip_value(&nested, 17, 0).is_err());
+        assert!(skip_value(&b"[".repeat(100_000), 0, 0).is_err());
+        assert!(strip(&nested).is_ok());
+
+        let mut info = pdf.clone();
+        let at = info
+            .windows(6)
+            .position(|window| window == b"Author")
+            .unwrap();
+        info[at - 2] = b'[';
+        assert!(strip(&info).is_err());
+        assert!(strip(b"PK\x03\x04 not a zip").is_err());
+        assert!(children("<a><b></a>", 0..10).is_err());
+        assert!(children("<a", 0..2).is_err());
+    }
+
+    #[tokio::test]
+    async fn documents_are_replaced_and_other_files_skipped() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        let files = [("report.pdf", pdf()), ("notes.txt", b"%PDF".to_vec())];
+
+        let mut outcomes = Vec::new();
+        for (name, content) in &files {
+            let path = root.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            let counters = Arc::new(Counters::new(1));
+            let mut entry = FileEntry::new(0, path, content.len() as u64, counters);
+            outcomes.push(StripDocumentMetadata.apply(&ctx, &mut entry).await);
+        }
+
+        assert!(matches!(outcomes[0], ActionOutcome::Applied));
+        assert!(matches!(outcomes[1], ActionOutcome::Skipped));
+        let read = |name: &str| std::fs::read(root.path().join(name)).unwrap();
+        assert_eq!(read("report.pdf"), strip(&pdf()).unwrap().unwrap().content);
+        assert_eq!(std::fs::read_dir(root.path()).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,669 @@
+use crate::gui::cleaner::action::{replace, ActionContext, ActionOutcome, FileAction, FileEntry};
+use crate::gui::cleaner::blocking;
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Not;
+use std::path::Path;
+
+/// Removes the EXIF, XMP and IPTC blocks of JPEG, PNG, TIFF and WebP images, GPS
+/// coordinates and camera serial numbers included, without re-encoding them.
+///
+/// Only the metadata blocks are cut out, the pixel data is copied as is. Files of other
+/// formats, camera raw files among them, and images without metadata are skipped. The
+/// kinds of metadata removed go to the report as `removed`.
+///
+/// The EXIF orientation goes too, so viewers may show rotated photos the other way up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripImageMetadata;
+
+/// An image without its metadata.
+#[derive(Debug)]
+pub struct Stripped {
+    pub content: Vec<u8>,
+    /// Kinds of metadata removed, `EXIF`, `XMP` or `IPTC`.
+    pub removed: BTreeSet<&'static str>,
+}
+
+/// Image formats [`StripImageMetadata`] handles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Jpeg,
+    Png,
+    Tiff,
+    WebP,
+}
+
+impl Format {
+    /// Bytes [`Format::detect`] looks at.
+    pub const SNIFF_LEN: usize = 12;
+
+    /// The format of the file `path` starting with `start`, if supported.
+    ///
+    /// Camera raw files, NEF, CR2, DNG, ARW and the like, are TIFF files too, but only
+    /// files named `.tif` or `.tiff` are taken as such so that raw files are left alone.
+    pub fn detect(start: &[u8], path: &Path) -> Option<Format> {
+        let extension = path.extension().unwrap_or_default();
+        let tiff = extension.eq_ignore_ascii_case("tif") || extension.eq_ignore_ascii_case("tiff");
+
+        if start.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Format::Jpeg)
+        } else if start.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Format::Png)
+        } else if (start.starts_with(b"II*\0") || start.starts_with(b"MM\0*")) && tiff {
+            Some(Format::Tiff)
+        } else if start.starts_with(b"RIFF") && start.get(8..12) == Some(b"WEBP") {
+            Some(Format::WebP)
+        } else {
+            None
+        }
+    }
+}
+
+#[async_trait]
+impl FileAction for StripImageMetadata {
+    fn label(&self) -> &str {
+        "Scrubbed"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        let format = match ctx.io.read_at(&entry.path, 0, Format::SNIFF_LEN).await {
+            Ok(start) => match Format::detect(&start, &entry.path) {
+                Some(format) => format,
+                None => return ActionOutcome::Skipped,
+            },
+            Err(err) => return ActionOutcome::Failed(err.into()),
+        };
+        let content = match ctx.io.read(&entry.path).await {
+            Ok(content) => content,
+            Err(err) => return ActionOutcome::Failed(err.into()),
+        };
+
+        let stripped = match blocking(move || strip(&content, format)).await {
+            Ok(stripped) if stripped.removed.is_empty().not() => stripped,
+            Ok(_) => return ActionOutcome::Skipped,
+            Err(err) => return ActionOutcome::Failed(err),
+        };
+        let removed = stripped.removed.iter().copied().collect::<Vec<_>>();
+        entry.details.insert("removed".to_string(), removed.into());
+
+        if let Err(err) = replace(ctx, &entry.path, stripped.content).await {
+            return ActionOutcome::Failed(err);
+        }
+        entry.advance(entry.len);
+
+        ActionOutcome::Applied
+    }
+}
+
+/// `content`, an image of `format`, without its metadata.
+pub fn strip(content: &[u8], format: Format) -> anyhow::Result<Stripped> {
+    let mut removed = BTreeSet::new();
+    if content.len() < Format::SNIFF_LEN {
+        bail!("the image is cut short");
+    }
+
+    let content = match format {
+        Format::Jpeg => strip_jpeg(content, &mut removed).context("reading the JPEG segments")?,
+        Format::Png => strip_png(content, &mut removed).context("reading the PNG chunks")?,
+        Format::Tiff => {
+            strip_tiff(content, &mut removed).context("reading the TIFF directories")?
+        }
+        Format::WebP => strip_webp(content, &mut removed).context("reading the WebP chunks")?,
+    };
+
+    Ok(Stripped { content, removed })
+}
+
+/// Copies the segments up to the image data, leaving out the metadata ones.
+fn strip_jpeg(content: &[u8], removed: &mut BTreeSet<&'static str>) -> anyhow::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(content.len());
+    output.extend_from_slice(&content[..2]);
+    let mut position = 2;
+
+    loop {
+        if content.get(position) != Some(&0xFF) {
+            bail!("no marker at offset {position}");
+        }
+        // Markers may be preceded by any number of fill bytes.
+        let mut marker = position + 1;
+        while content.get(marker) == Some(&0xFF) {
+            marker += 1;
+        }
+
+        let end = match content.get(marker) {
+            None => bail!("truncated marker at offset {position}"),
+            // Start of scan or end of image, the rest is image data.
+            Some(0xDA | 0xD9) => {
+                output.extend_from_slice(&content[position..]);
+                return Ok(output);
+            }
+            // Markers without a length.
+            Some(0x01 | 0xD0..=0xD7) => marker + 1,
+            Some(_) => {
+                let len = read_u16(content, marker + 1, true)? as usize;
+                if len < 2 || marker + 1 + len > content.len() {
+                    bail!("truncated segment at offset {position}");
+                }
+                marker + 1 + len
+            }
+        };
+
+        let payload = content.get(marker + 3..end).unwrap_or_default();
+        match jpeg_metadata(content[marker], payload) {
+            Some(kind) => {
+                removed.insert(kind);
+            }
+            None => output.extend_from_slice(&content[position..end]),
+        }
+        position = end;
+    }
+}
+
+fn jpeg_metadata(marker: u8, payload: &[u8]) -> Option<&'static str> {
+    match marker {
+        0xE1 if payload.starts_with(b"Exif\0") => Some("EXIF"),
+        0xE1 if payload.starts_with(b"http://ns.adobe.com/xap/1.0/\0")
+            || payload.starts_with(b"http://ns.adobe.com/xmp/extension/\0") =>
+        {
+            Some("XMP")
+        }
+        0xED if payload.starts_with(b"Photoshop 3.0\0") => Some("IPTC"),
+        _ => None,
+    }
+}
+
+/// Copies the chunks, leaving out `eXIf` and the text chunks holding metadata.
+fn strip_png(content: &[u8], removed: &mut BTreeSet<&'static str>) -> anyhow::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(content.len());
+    output.extend_from_slice(&content[..8]);
+    let mut position = 8;
+
+    while position < content.len() {
+        let len = read_u32(content, position, true)? as usize;
+        // Length, type, data and CRC.
+        let end = position + 12 + len;
+        if end > content.len() {
+            bail!("truncated chunk at offset {position}");
+        }
+        let kind = &content[position + 4..position + 8];
+
+        match png_metadata(kind, &content[position + 8..end - 4]) {
+            Some(metadata) => {
+                removed.insert(metadata);
+            }
+            None => output.extend_from_slice(&content[position..end]),
+        }
+        position = end;
+
+        if kind == b"IEND" {
+            output.extend_from_slice(&content[position..]);
+            break;
+        }
+    }
+
+    Ok(output)
+}
+
+fn png_metadata(kind: &[u8], data: &[u8]) -> Option<&'static str> {
+    if kind == b"eXIf" {
+        return Some("EXIF");
+    }
+    if matches!(kind, b"tEXt" | b"zTXt" | b"iTXt").not() {
+        return None;
+    }
+
+    // Profiles written by ImageMagick and exiftool are named after their kind.
+    match data.split(|byte| *byte == 0).next()? {
+        b"XML:com.adobe.xmp" | b"Raw profile type xmp" => Some("XMP"),
+        b"Raw profile type exif" | b"Raw profile type APP1" => Some("EXIF"),
+        b"Raw profile type iptc" | b"Raw profile type 8bim" => Some("IPTC"),
+        _ => None,
+    }
+}
+
+/// Copies the chunks, leaving out `EXIF` and `XMP `, and clears their flags in the
+/// `VP8X` header.
+fn strip_webp(content: &[u8], removed: &mut BTreeSet<&'static str>) -> anyhow::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(content.len());
+    output.extend_from_slice(&content[..12]);
+    let mut position = 12;
+
+    while position + 8 <= content.len() {
+        let len = read_u32(content, position + 4, false)? as usize;
+        // Chunks are padded to an even length.
+        let end = (position + 8 + len + len % 2).min(content.len());
+        if position + 8 + len > content.len() {
+            bail!("truncated chunk at offset {position}");
+        }
+
+        match &content[position..position + 4] {
+            b"EXIF" => {
+                removed.insert("EXIF");
+            }
+            b"XMP " => {
+                removed.insert("XMP");
+            }
+            kind => {
+                let start = output.len();
+                output.extend_from_slice(&content[position..end]);
+                if kind == b"VP8X" && len > 0 {
+                    output[start + 8] &= !(VP8X_EXIF | VP8X_XMP);
+                }
+            }
+        }
+        position = end;
+    }
+
+    let riff_len = u32::try_from(output.len() - 8).context("the image is too large")?;
+    output[4..8].copy_from_slice(&riff_len.to_le_bytes());
+
+    Ok(output)
+}
+
+const VP8X_EXIF: u8 = 0x08;
+const VP8X_XMP: u8 = 0x04;
+
+/// TIFF tags holding metadata rather than describing the image.
+fn tiff_metadata(tag: u16) -> Option<&'static str> {
+    match tag {
+        // Description, make, model, software, date, artist, host computer, copyright.
+        270..=272 | 305 | 306 | 315 | 316 | 33432 => Some("EXIF"),
+        // The EXIF, GPS and interoperability directories.
+        EXIF_IFD | GPS_IFD | INTEROPERABILITY_IFD => Some("EXIF"),
+        700 => Some("XMP"),
+        // IPTC, and Photoshop resources which embed it.
+        33723 | 34377 => Some("IPTC"),
+        _ => None,
+    }
+}
+
+/// Tags of camera raw images, DNGVersion and CFAPattern, whose sensor data must not be
+/// touched.
+const RAW_TAGS: [u16; 2] = [50706, 33422];
+
+const EXIF_IFD: u16 = 34665;
+const GPS_IFD: u16 = 34853;
+const INTEROPERABILITY_IFD: u16 = 40965;
+/// Directories found under a main one, interoperability in EXIF at most in valid files.
+const MAX_DEPTH: usize = 4;
+
+/// Removes the metadata tags from every directory of the file and zeroes their
+/// values, which stay where they were so that no offset changes.
+fn strip_tiff(content: &[u8], removed: &mut BTreeSet<&'static str>) -> anyhow::Result<Vec<u8>> {
+    let mut tiff = Tiff {
+        data: content.to_vec(),
+        big_endian: content.starts_with(b"MM"),
+        visited: HashSet::new(),
+    };
+
+    let mut offset = tiff.u32(4)? as usize;
+    while offset != 0 && tiff.visited.insert(offset) {
+        offset = tiff.strip_directory(offset, removed)?;
+    }
+
+    Ok(tiff.data)
+}
+
+struct Tiff {
+    data: Vec<u8>,
+    big_endian: bool,
+    /// Directories already gone through, which a corrupt file may link in a loop.
+    visited: HashSet<usize>,
+}
+
+impl Tiff {
+    fn u16(&self, offset: usize) -> anyhow::Result<u16> {
+        read_u16(&self.data, offset, self.big_endian)
+    }
+
+    fn u32(&self, offset: usize) -> anyhow::Result<u32> {
+        read_u32(&self.data, offset, self.big_endian)
+    }
+
+    /// Removes the metadata entries of the directory at `offset` and returns the
+    /// offset of the next directory.
+    fn strip_directory(
+        &mut self,
+        offset: usize,
+        removed: &mut BTreeSet<&'static str>,
+    ) -> anyhow::Result<usize> {
+        let count = self.u16(offset)? as usize;
+        let end = offset + 2 + 12 * count;
+        let next = self.u32(end)? as usize;
+
+        let mut kept = Vec::with_capacity(12 * count);
+        for entry in (offset + 2..end).step_by(12) {
+            let tag = self.u16(entry)?;
+            if RAW_TAGS.contains(&tag) {
+                bail!("camera raw data, tag {tag} at offset {entry}");
+            }
+            match tiff_metadata(tag) {
+                Some(kind) => {
+                    removed.insert(kind);
+                    self.erase_entry(entry, 0)?;
+                }
+                None => kept.extend_from_slice(&self.data[entry..entry + 12]),
+            }
+        }
+        if kept.len() == 12 * count {
+            return Ok(next);
+        }
+
+        // The remaining entries move up, still sorted, followed by the next offset.
+        let count = (kept.len() / 12) as u16;
+        let count = match self.big_endian {
+            true => count.to_be_bytes(),
+            false => count.to_le_bytes(),
+        };
+        self.data[offset..offset + 2].copy_from_slice(&count);
+        let next_at = offset + 2 + kept.len();
+        self.data[offset + 2..next_at].copy_from_slice(&kept);
+        self.data.copy_within(end..end + 4, next_at);
+        self.data[next_at + 4..end + 4].fill(0);
+
+        Ok(next)
+    }
+
+    /// Zeroes the value of the entry at `entry`, and the directory it points to if
+    /// it is one, `depth` directories down from the main ones.
+    fn erase_entry(&mut self, entry: usize, depth: usize) -> anyhow::Result<()> {
+        let tag = self.u16(entry)?;
+        let kind = self.u16(entry + 2)?;
+        let count = self.u32(entry + 4)? as usize;
+        let size = match kind {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 | 13 => 4,
+            5 | 10 | 12 => 8,
+            _ => 0,
+        } * count;
+
+        if matches!(tag, EXIF_IFD | GPS_IFD | INTEROPERABILITY_IFD) {
+            let directory = self.u32(entry + 8)? as usize;
+            if depth == MAX_DEPTH {
+                bail!("directories nested too deep at offset {entry}");
+            }
+            if self.visited.insert(directory) {
+                self.erase_directory(directory, depth + 1)?;
+            }
+        }
+        if size > 4 {
+            let value = self.u32(entry + 8)? as usize;
+            match self.data.get_mut(value..value + size) {
+                Some(value) => value.fill(0),
+                None => bail!("value out of the file at offset {value}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Zeroes every entry of the directory at `offset`, with their values.
+    fn erase_directory(&mut self, offset: usize, depth: usize) -> anyhow::Result<()> {
+        let count = self.u16(offset)? as usize;
+        let end = offset + 2 + 12 * count + 4;
+        if end > self.data.len() {
+            bail!("directory out of the file at offset {offset}");
+        }
+
+        for entry in (offset + 2..end - 4).step_by(12) {
+            self.erase_entry(entry, depth)?;
+        }
+        self.data[offset..end].fill(0);
+
+        Ok(())
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize, big_endian: bool) -> anyhow::Result<u16> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .with_context(|| format!("unexpected end of file at offset {offset}"))?;
+    let bytes = [bytes[0], bytes[1]];
+
+    Ok(match big_endian {
+        true => u16::from_be_bytes(bytes),
+        false => u16::from_le_bytes(bytes),
+    })
+}
+
+fn read_u32(data: &[u8], offset: usize, big_endian: bool) -> anyhow::Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .with_context(|| format!("unexpected end of file at offset {offset}"))?;
+    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+
+    Ok(match big_endian {
+        true => u32::from_be_bytes(bytes),
+        false => u32::from_le_bytes(bytes),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui::cleaner::action::Counters;
+    use crate::gui::cleaner::io::TokioBackend;
+    use std::sync::Arc;
+
+    const SCAN: &[u8] = b"\xFF\xDA\x00\x04\x01\x02pixels\xFF\xD9";
+
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut segment = vec![0xFF, marker];
+        segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        segment.extend_from_slice(payload);
+        segment
+    }
+
+    fn jpeg() -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend(segment(0xE0, b"JFIF\0\x01\x02"));
+        jpeg.extend(segment(0xE1, b"Exif\0\0GPS"));
+        jpeg.extend(segment(0xE1, b"http://ns.adobe.com/xap/1.0/\0<x/>"));
+        jpeg.extend(segment(0xED, b"Photoshop 3.0\0IPTC"));
+        jpeg.extend(segment(0xDB, b"tables"));
+        jpeg.extend_from_slice(SCAN);
+        jpeg
+    }
+
+    fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0; 4]);
+        chunk
+    }
+
+    fn png() -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend(chunk(b"IHDR", &[0; 13]));
+        png.extend(chunk(b"tEXt", b"XML:com.adobe.xmp\0<x/>"));
+        png.extend(chunk(b"tEXt", b"Comment\0kept"));
+        png.extend(chunk(b"eXIf", b"MM\0*"));
+        png.extend(chunk(b"IDAT", b"pixels"));
+        png.extend(chunk(b"IEND", b""));
+        png
+    }
+
+    fn riff_chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = kind.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn webp() -> Vec<u8> {
+        let mut body = b"WEBP".to_vec();
+        body.extend(riff_chunk(
+            b"VP8X",
+            &[VP8X_EXIF | VP8X_XMP | 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        ));
+        body.extend(riff_chunk(b"VP8L", b"pixels"));
+        body.extend(riff_chunk(b"EXIF", b"odd"));
+        body.extend(riff_chunk(b"XMP ", b"<x/>"));
+
+        let mut webp = b"RIFF".to_vec();
+        webp.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        webp.extend(body);
+        webp
+    }
+
+    /// A little-endian TIFF with a width, a camera make, the pixels and `extra` entries
+    /// of type LONG.
+    fn tiff(extra: &[(u16, u32)]) -> Vec<u8> {
+        let mut entries = vec![(256, 3, 1, 16), (271, 2, 8, 100), (273, 4, 1, 120)];
+        entries.extend(extra.iter().map(|(tag, value)| (*tag, 4, 1, *value)));
+        entries.sort();
+
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend_from_slice(&8u32.to_le_bytes());
+        tiff.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for (tag, kind, count, value) in entries {
+            tiff.extend_from_slice(&u16::to_le_bytes(tag));
+            tiff.extend_from_slice(&u16::to_le_bytes(kind));
+            tiff.extend_from_slice(&u32::to_le_bytes(count));
+            tiff.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        tiff.extend_from_slice(&[0; 4]);
+        tiff.resize(100, 0);
+        tiff.extend_from_slice(b"Camera\0\0");
+        tiff.resize(120, 0);
+        tiff.extend_from_slice(b"pixels");
+        tiff
+    }
+
+    fn context(root: &Path) -> ActionContext {
+        ActionContext {
+            root: root.to_path_buf(),
+            outputs: Default::default(),
+            io: Arc::new(TokioBackend),
+        }
+    }
+
+    #[test]
+    fn jpeg_metadata_segments_are_cut() {
+        let stripped = strip(&jpeg(), Format::Jpeg).unwrap();
+
+        let mut expected = vec![0xFF, 0xD8];
+        expected.extend(segment(0xE0, b"JFIF\0\x01\x02"));
+        expected.extend(segment(0xDB, b"tables"));
+        expected.extend_from_slice(SCAN);
+        assert_eq!(stripped.content, expected);
+        assert_eq!(stripped.removed, BTreeSet::from(["EXIF", "IPTC", "XMP"]));
+    }
+
+    #[test]
+    fn png_metadata_chunks_are_cut() {
+        let stripped = strip(&png(), Format::Png).unwrap();
+
+        let mut expected = b"\x89PNG\r\n\x1a\n".to_vec();
+        expected.extend(chunk(b"IHDR", &[0; 13]));
+        expected.extend(chunk(b"tEXt", b"Comment\0kept"));
+        expected.extend(chunk(b"IDAT", b"pixels"));
+        expected.extend(chunk(b"IEND", b""));
+        assert_eq!(stripped.content, expected);
+        assert_eq!(stripped.removed, BTreeSet::from(["EXIF", "XMP"]));
+    }
+
+    #[test]
+    fn webp_metadata_chunks_and_flags_are_cut() {
+        let stripped = strip(&webp(), Format::WebP).unwrap();
+        let content = &stripped.content;
+
+        let riff_len = u32::from_le_bytes(content[4..8].try_into().unwrap()) as usize;
+        assert_eq!(riff_len, content.len() - 8);
+        assert_eq!(content[20], 0x10);
+        assert_eq!(&content[30..44], riff_chunk(b"VP8L", b"pixels"));
+        assert_eq!(content.len(), 44);
+        assert_eq!(stripped.removed, BTreeSet::from(["EXIF", "XMP"]));
+    }
+
+    #[test]
+    fn tiff_metadata_entries_are_removed_in_place() {
+        let original = tiff(&[]);
+        let stripped = strip(&original, Format::Tiff).unwrap();
+        let content = &stripped.content;
+
+        assert_eq!(content.len(), original.len());
+        assert_eq!(read_u16(content, 8, false).unwrap(), 2);
+        assert_eq!(read_u16(content, 10, false).unwrap(), 256);
+        assert_eq!(read_u16(content, 22, false).unwrap(), 273);
+        assert_eq!(read_u32(content, 34, false).unwrap(), 0);
+        assert_eq!(content[100..108], [0; 8]);
+        assert_eq!(&content[120..], b"pixels");
+        assert_eq!(stripped.removed, BTreeSet::from(["EXIF"]));
+    }
+
+    #[test]
+    fn raw_images_are_left_alone() {
+        let start = &tiff(&[])[..Format::SNIFF_LEN];
+        assert_eq!(
+            Format::detect(start, Path::new("a.TIF")),
+            Some(Format::Tiff)
+        );
+        assert_eq!(
+            Format::detect(start, Path::new("a.tiff")),
+            Some(Format::Tiff)
+        );
+        for raw in ["a.nef", "a.CR2", "a.dng", "a.arw", "a.orf", "a"] {
+            assert_eq!(Format::detect(start, Path::new(raw)), None, "{raw}");
+        }
+
+        assert!(strip(&tiff(&[(50706, 1)]), Format::Tiff).is_err());
+        assert!(strip(&tiff(&[(33422, 1)]), Format::Tiff).is_err());
+    }
+
+    #[test]
+    fn malformed_images_fail_without_panicking() {
+        let images = [
+            (jpeg(), Format::Jpeg),
+            (png(), Format::Png),
+            (webp(), Format::WebP),
+            (tiff(&[(EXIF_IFD, 130), (GPS_IFD, 8)]), Format::Tiff),
+        ];
+
+        for (image, format) in images {
+            for len in 0..image.len() {
+                let _ = strip(&image[..len], format);
+            }
+        }
+        assert!(strip(&jpeg()[..20], Format::Jpeg).is_err());
+        assert!(strip(&png()[..40], Format::Png).is_err());
+        assert!(strip(&tiff(&[])[..40], Format::Tiff).is_err());
+    }
+
+    #[tokio::test]
+    async fn images_are_replaced_and_other_files_skipped() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        let files = [
+            ("photo.jpg", jpeg()),
+            ("raw.nef", tiff(&[])),
+            ("notes.txt", b"text".to_vec()),
+        ];
+
+        let mut outcomes = Vec::new();
+        for (name, content) in &files {
+            let path = root.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            let counters = Arc::new(Counters::new(1));
+            let mut entry = FileEntry::new(0, path, content.len() as u64, counters);
+            outcomes.push(StripImageMetadata.apply(&ctx, &mut entry).await);
+        }
+
+        assert!(matches!(outcomes[0], ActionOutcome::Applied));
+        assert!(matches!(outcomes[1], ActionOutcome::Skipped));
+        assert!(matches!(outcomes[2], ActionOutcome::Skipped));
+        let read = |name: &str| std::fs::read(root.path().join(name)).unwrap();
+        assert_eq!(
+            read("photo.jpg"),
+            strip(&jpeg(), Format::Jpeg).unwrap().content
+        );
+        assert_eq!(read("raw.nef"), files[1].1);
+        assert_eq!(std::fs::read_dir(root.path()).unwrap().count(), 3);
+    }
+}
@@ -0,0 +1,575 @@
+use async_trait::async_trait;
+use std::fmt::Debug;
+use std::io::{self, SeekFrom};
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+pub type Progress<'a> = dyn Fn(u64) + Send + Sync + 'a;
+
+/// What [`IoBackend::fill`] writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fill {
+    Zeros,
+    Random,
+}
+
+impl Fill {
+    /// Makes `buffer` a chunk of `len` bytes to write.
+    fn prepare(&self, buffer: &mut Vec<u8>, len: usize) {
+        buffer.resize(len, 0);
+        if *self == Fill::Random {
+            rand::Rng::fill(&mut rand::thread_rng(), buffer.as_mut_slice());
+        }
+    }
+}
+
+/// File system operations used to wipe files.
+#[async_trait]
+pub trait IoBackend: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Reads up to `len` bytes of `path` from `offset` on. Fewer bytes are returned only
+    /// at the end of the file.
+    async fn read_at(&self, path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>>;
+
+    /// Reads the whole of `path`.
+    async fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut content = Vec::new();
+
+        loop {
+            let chunk = self.read_at(path, content.len() as u64, CHUNK_SIZE).await?;
+            let end = chunk.len() < CHUNK_SIZE;
+            content.extend_from_slice(&chunk);
+            if end {
+                return Ok(content);
+            }
+        }
+    }
+
+    /// Replaces the content of `path` with `content`, creating the file if needed.
+    async fn write(&self, path: &Path, content: Vec<u8>) -> io::Result<()>;
+
+    /// Writes `content` to the existing file `path` at `offset`, keeping the rest of it.
+    async fn write_at(&self, path: &Path, offset: u64, content: Vec<u8>) -> io::Result<()>;
+
+    /// Overwrites `len` bytes of `path` from `offset` on with zeros or random bytes,
+    /// keeping its size.
+    ///
+    /// `progress` is called with the position reached in the file after every chunk.
+    async fn fill(
+        &self,
+        path: &Path,
+        offset: u64,
+        len: u64,
+        fill: Fill,
+        progress: &Progress<'_>,
+    ) -> io::Result<()>;
+
+    /// Flushes the content and metadata of `path` to disk.
+    async fn fsync(&self, path: &Path) -> io::Result<()>;
+
+    /// Removes the file `path`.
+    async fn remove(&self, path: &Path) -> io::Result<()>;
+}
+
+/// Runs every operation on the `tokio::fs` blocking thread pool.
+#[derive(Debug, Default)]
+pub struct TokioBackend;
+
+#[async_trait]
+impl IoBackend for TokioBackend {
+    fn name(&self) -> &'static str {
+        "tokio"
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        tokio::fs::rename(from, to).await
+    }
+
+    async fn read_at(&self, path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut content = Vec::with_capacity(len);
+
+        file.seek(SeekFrom::Start(offset)).await?;
+        file.take(len as u64).read_to_end(&mut content).await?;
+        Ok(content)
+    }
+
+    async fn write(&self, path: &Path, content: Vec<u8>) -> io::Result<()> {
+        tokio::fs::write(path, content).await
+    }
+
+    async fn write_at(&self, path: &Path, offset: u64, content: Vec<u8>) -> io::Result<()> {
+        let mut file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+
+        file.seek(SeekFrom::Start(offset)).await?;
+        file.write_all(&content).await?;
+        file.flush().await
+    }
+
+    async fn fill(
+        &self,
+        path: &Path,
+        offset: u64,
+        len: u64,
+        fill: Fill,
+        progress: &Progress<'_>,
+    ) -> io::Result<()> {
+        let mut file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+        let mut buffer = Vec::with_capacity(CHUNK_SIZE);
+        let end = offset + len;
+        let mut position = file.seek(SeekFrom::Start(offset)).await?;
+
+        while position < end {
+            let chunk = (end - position).min(CHUNK_SIZE as u64) as usize;
+            fill.prepare(&mut buffer, chunk);
+            file.write_all(&buffer).await?;
+            position += chunk as u64;
+            progress(position);
+        }
+
+        file.flush().await
+    }
+
+    async fn fsync(&self, path: &Path) -> io::Result<()> {
+        let file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+        file.sync_all().await
+    }
+
+    async fn remove(&self, path: &Path) -> io::Result<()> {
+        tokio::fs::remove_file(path).await
+    }
+}
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub use uring::UringBackend;
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring {
+    use super::{Fill, IoBackend, Progress, CHUNK_SIZE};
+    use async_trait::async_trait;
+    use io_uring::{opcode, squeue, types, IoUring};
+    use std::collections::HashMap;
+    use std::ffi::CString;
+    use std::io;
+    use std::ops::Not;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::RawFd;
+    use std::path::Path;
+    use std::sync::mpsc;
+    use tokio::sync::oneshot;
+
+    const ENTRIES: u32 = 256;
+
+    /// Submits operations to an io_uring owned by a dedicated thread.
+    ///
+    /// Operations of concurrent workers share the ring, so a single `io_uring_enter`
+    /// submits and reaps many of them at once. The ring thread exits when the
+    /// backend is dropped and the operations in flight have completed.
+    #[derive(Debug)]
+    pub struct UringBackend {
+        sender: mpsc::Sender<Request>,
+    }
+
+    #[derive(Debug)]
+    struct Request {
+        op: Op,
+        reply: oneshot::Sender<(io::Result<i32>, Op)>,
+    }
+
+    /// An operation together with the buffers the kernel uses while it is in flight.
+    /// The operation is handed back on completion so that buffers can be reused.
+    #[derive(Debug)]
+    enum Op {
+        Rename(CString, CString),
+        Unlink(CString),
+        Open(CString, i32),
+        Read(RawFd, Vec<u8>, u64),
+        Write(RawFd, Vec<u8>, u64),
+        Fsync(RawFd),
+        Close(RawFd),
+    }
+
+    impl Op {
+        fn entry(&mut self) -> squeue::Entry {
+            let cwd = types::Fd(libc::AT_FDCWD);
+
+            match self {
+                Op::Rename(from, to) => {
+                    opcode::RenameAt::new(cwd, from.as_ptr(), cwd, to.as_ptr()).build()
+                }
+                Op::Unlink(path) => opcode::UnlinkAt::new(cwd, path.as_ptr()).build(),
+                Op::Open(path, flags) => opcode::OpenAt::new(cwd, path.as_ptr())
+                    .flags(*flags | libc::O_CLOEXEC)
+                    .mode(0o644)
+                    .build(),
+                Op::Read(fd, buffer, offset) => {
+                    let spare = buffer.spare_capacity_mut();
+
+                    opcode::Read::new(
+                        types::Fd(*fd),
+                        spare.as_mut_ptr().cast(),
+                        spare.len() as u32,
+                    )
+                    .offset64(*offset as i64)
+                    .build()
+                }
+                Op::Write(fd, buffer, offset) => {
+                    opcode::Write::new(types::Fd(*fd), buffer.as_ptr(), buffer.len() as u32)
+                        .offset64(*offset as i64)
+                        .build()
+                }
+                Op::Fsync(fd) => opcode::Fsync::new(types::Fd(*fd)).build(),
+                Op::Close(fd) => opcode::Close::new(types::Fd(*fd)).build(),
+            }
+        }
+    }
+
+    impl UringBackend {
+        pub fn new() -> io::Result<Self> {
+            let ring = IoUring::new(ENTRIES)?;
+            let (sender, receiver) = mpsc::channel();
+
+            std::thread::Builder::new()
+                .name("rutabaga-uring".to_string())
+                .spawn(move || drive(ring, receiver))?;
+
+            Ok(Self { sender })
+        }
+
+        async fn submit(&self, op: Op) -> (io::Result<i32>, Option<Op>) {
+            let (reply, completion) = oneshot::channel();
+            let stopped = || io::Error::new(io::ErrorKind::BrokenPipe, "io_uring thread stopped");
+
+            if let Err(mpsc::SendError(request)) = self.sender.send(Request { op, reply }) {
+                return (Err(stopped()), Some(request.op));
+            }
+
+            match completion.await {
+                Ok((result, op)) => (result, Some(op)),
+                Err(_) => (Err(stopped()), None),
+            }
+        }
+
+        async fn open(&self, path: &Path, flags: i32) -> io::Result<RawFd> {
+            self.submit(Op::Open(c_path(path)?, flags)).await.0
+        }
+
+        /// Reads up to `len` bytes at `offset`, resubmitting after short reads until the
+        /// end of the file.
+        async fn read_at_fd(&self, fd: RawFd, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+            let mut op = Op::Read(fd, Vec::with_capacity(len), offset);
+
+            loop {
+                let (result, returned) = self.submit(op).await;
+                let (mut buffer, offset) = match returned {
+                    Some(Op::Read(_, buffer, offset)) => (buffer, offset),
+                    _ => return result.map(|_| Vec::new()),
+                };
+
+                let count = result? as usize;
+                // SAFETY: the kernel initialized `count` bytes of the spare capacity.
+                unsafe { buffer.set_len(buffer.len() + count) };
+                if count == 0 || buffer.len() == len {
+                    return Ok(buffer);
+                }
+
+                op = Op::Read(fd, buffer, offset + count as u64);
+            }
+        }
+
+        /// Writes all of `buffer` at `offset`, resubmitting after short writes, and
+        /// returns the buffer.
+        async fn write_at_fd(
+            &self,
+            fd: RawFd,
+            buffer: Vec<u8>,
+            offset: u64,
+        ) -> io::Result<Vec<u8>> {
+            let len = buffer.len();
+            let mut op = Op::Write(fd, buffer, offset);
+            let mut written = 0;
+
+            loop {
+                let (result, returned) = self.submit(op).await;
+                let (mut buffer, offset) = match returned {
+                    Some(Op::Write(_, buffer, offset)) => (buffer, offset),
+                    _ => return result.map(|_| Vec::new()),
+                };
+
+                let count = result? as usize;
+                written += count;
+                if written == len {
+                    // Short writes drained the buffer, hand back one of the original size.
+                    buffer.resize(len, 0);
+                    return Ok(buffer);
+                }
+                if count == 0 {
+                    return Err(io::ErrorKind::WriteZero.into());
+                }
+
+                buffer.drain(..count);
+                op = Op::Write(fd, buffer, offset + count as u64);
+            }
+        }
+
+        /// Runs `f` on the file opened with `flags`, closing it whatever the outcome.
+        async fn with_file<'a, T, F, Fut>(&'a self, path: &Path, flags: i32, f: F) -> io::Result<T>
+        where
+            F: FnOnce(RawFd) -> Fut,
+            Fut: std::future::Future<Output = io::Result<T>> + 'a,
+        {
+            let fd = self.open(path, flags).await?;
+            let result = f(fd).await;
+            let (closed, _) = self.submit(Op::Close(fd)).await;
+
+            result.and_then(|value| closed.map(|_| value))
+        }
+    }
+
+    #[async_trait]
+    impl IoBackend for UringBackend {
+        fn name(&self) -> &'static str {
+            "io_uring"
+        }
+
+        async fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            let op = Op::Rename(c_path(from)?, c_path(to)?);
+
+            self.submit(op).await.0.map(|_| ())
+        }
+
+        async fn read_at(&self, path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+            self.with_file(path, libc::O_RDONLY, |fd| self.read_at_fd(fd, offset, len))
+                .await
+        }
+
+        async fn write(&self, path: &Path, content: Vec<u8>) -> io::Result<()> {
+            let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC;
+
+            self.with_file(path, flags, |fd| async move {
+                if content.is_empty().not() {
+                    self.write_at_fd(fd, content, 0).await?;
+                }
+                Ok(())
+            })
+            .await
+        }
+
+        async fn write_at(&self, path: &Path, offset: u64, content: Vec<u8>) -> io::Result<()> {
+            self.with_file(path, libc::O_WRONLY, |fd| async move {
+                if content.is_empty().not() {
+                    self.write_at_fd(fd, content, offset).await?;
+                }
+                Ok(())
+            })
+            .await
+        }
+
+        async fn fill(
+            &self,
+            path: &Path,
+            offset: u64,
+            len: u64,
+            fill: Fill,
+            progress: &Progress<'_>,
+        ) -> io::Result<()> {
+            self.with_file(path, libc::O_WRONLY, |fd| async move {
+                let mut buffer = Vec::new();
+                let end = offset + len;
+                let mut position = offset;
+
+                while position < end {
+                    let chunk = (end - position).min(CHUNK_SIZE as u64) as usize;
+                    fill.prepare(&mut buffer, chunk);
+                    buffer = self.write_at_fd(fd, buffer, position).await?;
+                    position += chunk as u64;
+                    progress(position);
+                }
+
+                Ok(())
+            })
+            .await
+        }
+
+        async fn fsync(&self, path: &Path) -> io::Result<()> {
+            self.with_file(path, libc::O_WRONLY, |fd| async move {
+                self.submit(Op::Fsync(fd)).await.0.map(|_| ())
+            })
+            .await
+        }
+
+        async fn remove(&self, path: &Path) -> io::Result<()> {
+            self.submit(Op::Unlink(c_path(path)?)).await.0.map(|_| ())
+        }
+    }
+
+    fn c_path(path: &Path) -> io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    }
+
+    /// Ring thread: queues incoming requests and hands every completion back to its caller.
+    fn drive(mut ring: IoUring, receiver: mpsc::Receiver<Request>) {
+        let mut in_flight = HashMap::<u64, Request>::new();
+        let mut next_id = 0u64;
+
+        loop {
+            // Nothing to wait for: sleep until a request arrives or the backend is dropped.
+            if in_flight.is_empty() {
+                match receiver.recv() {
+                    Ok(request) => push(&mut ring, &mut in_flight, &mut next_id, request),
+                    Err(_) => return,
+                }
+            }
+
+            while ring.submission().is_full().not() {
+                match receiver.try_recv() {
+                    Ok(request) => push(&mut ring, &mut in_flight, &mut next_id, request),
+                    Err(_) => break,
+                }
+            }
+
+            if let Err(err) = ring.submit_and_wait(1) {
+                let retry = err.kind() == io::ErrorKind::Interrupted
+                    || err.raw_os_error() == Some(libc::EBUSY);
+
+                if retry.not() {
+                    // The kernel may still own the buffers of the operations in flight,
+                    // leak them rather than free memory it could write to.
+                    for (_, request) in in_flight.drain() {
+                        let Request { op, reply } = request;
+                        let error = io::Error::new(err.kind(), err.to_string());
+
+                        std::mem::forget(op);
+                        drop(reply.send((Err(error), Op::Close(-1))));
+                    }
+                    return;
+                }
+            }
+
+            for cqe in ring.completion() {
+                if let Some(Request { op, reply }) = in_flight.remove(&cqe.user_data()) {
+                    let result = match cqe.result() {
+                        result if result < 0 => Err(io::Error::from_raw_os_error(-result)),
+                        result => Ok(result),
+                    };
+                    let _ = reply.send((result, op));
+                }
+            }
+        }
+    }
+
+    fn push(
+        ring: &mut IoUring,
+        in_flight: &mut HashMap<u64, Request>,
+        next_id: &mut u64,
+        mut request: Request,
+    ) {
+        let id = *next_id;
+        *next_id = next_id.wrapping_add(1);
+
+        let entry = request.op.entry().user_data(id);
+        // SAFETY: the buffers referenced by the entry belong to `request`, which stays in
+        // `in_flight` until the kernel reports the operation as completed.
+        match unsafe { ring.submission().push(&entry) } {
+            Ok(_) => {
+                in_flight.insert(id, request);
+            }
+            Err(_) => {
+                let error = io::Error::other("submission queue full");
+                let _ = request.reply.send((Err(error), request.op));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::Not;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    fn backends() -> Vec<Arc<dyn IoBackend>> {
+        #[allow(unused_mut)]
+        let mut backends: Vec<Arc<dyn IoBackend>> = vec![Arc::new(TokioBackend)];
+
+        #[cfg(all(feature = "io-uring", target_os = "linux"))]
+        if let Ok(backend) = UringBackend::new() {
+            backends.push(Arc::new(backend));
+        }
+
+        backends
+    }
+
+    #[tokio::test]
+    async fn backends_read_and_write_ranges() {
+        for io in backends() {
+            let root = tempfile::tempdir().unwrap();
+            let path = root.path().join("file");
+
+            io.write(&path, b"0123456789".to_vec()).await.unwrap();
+            assert_eq!(
+                io.read(&path).await.unwrap(),
+                b"0123456789",
+                "{}",
+                io.name()
+            );
+            assert_eq!(io.read_at(&path, 2, 3).await.unwrap(), b"234");
+            assert_eq!(io.read_at(&path, 8, 16).await.unwrap(), b"89");
+            assert_eq!(io.read_at(&path, 20, 16).await.unwrap(), b"");
+
+            io.write_at(&path, 8, b"89ab".to_vec()).await.unwrap();
+            io.write_at(&path, 1, b"x".to_vec()).await.unwrap();
+            assert_eq!(
+                io.read(&path).await.unwrap(),
+                b"0x23456789ab",
+                "{}",
+                io.name()
+            );
+            assert!(io
+                .write_at(&root.path().join("missing"), 0, vec![1])
+                .await
+                .is_err());
+
+            let large: Vec<u8> = (0..CHUNK_SIZE * 2 + 3).map(|i| i as u8).collect();
+            io.write(&path, large.clone()).await.unwrap();
+            assert_eq!(io.read(&path).await.unwrap(), large, "{}", io.name());
+
+            let renamed = root.path().join("renamed");
+            io.rename(&path, &renamed).await.unwrap();
+            io.fsync(&renamed).await.unwrap();
+            io.remove(&renamed).await.unwrap();
+            assert!(renamed.exists().not());
+            assert!(io.read(&renamed).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn fill_keeps_the_size_and_reports_progress() {
+        for io in backends() {
+            let root = tempfile::tempdir().unwrap();
+            let path = root.path().join("file");
+            io.write(&path, vec![0xff; CHUNK_SIZE + 10]).await.unwrap();
+
+            let reached = AtomicU64::new(0);
+            io.fill(&path, 5, CHUNK_SIZE as u64, Fill::Zeros, &|position| {
+                reached.store(position, Ordering::SeqCst)
+            })
+            .await
+            .unwrap();
+
+            let content = io.read(&path).await.unwrap();
+            assert_eq!(content.len(), CHUNK_SIZE + 10, "{}", io.name());
+            assert_eq!(content[..5], [0xff; 5]);
+            assert!(content[5..CHUNK_SIZE + 5].iter().all(|byte| *byte == 0));
+            assert_eq!(content[CHUNK_SIZE + 5..], [0xff; 5]);
+            assert_eq!(reached.load(Ordering::SeqCst), CHUNK_SIZE as u64 + 5);
+        }
+    }
+}
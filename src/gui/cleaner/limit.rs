@@ -0,0 +1,346 @@
+use crate::gui::cleaner::io::{Fill, IoBackend, Progress};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::io;
+use std::ops::Not;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Largest amount of filler written between two checks of the byte limit.
+const THROTTLE_CHUNK: u64 = 1024 * 1024;
+
+/// How often the idle check samples `/proc`.
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The system is busy above this 1-minute load average per CPU.
+const MAX_LOAD_PER_CPU: f64 = 0.5;
+
+/// The system is busy when a disk spent more than this share of the time doing I/O.
+const MAX_DISK_UTILIZATION: f64 = 0.5;
+
+/// I/O budget of a job. Limits are read on every operation, so changing them
+/// affects a job that is already running.
+#[derive(Debug, Default)]
+pub struct Limits {
+    /// Bytes read or written per second, 0 when unlimited.
+    bytes_per_second: AtomicU64,
+    /// Backend operations per second, 0 when unlimited.
+    ops_per_second: AtomicU64,
+    when_idle: AtomicBool,
+    bytes: Mutex<Bucket>,
+    ops: Mutex<Bucket>,
+    probe: Mutex<IdleProbe>,
+}
+
+impl Limits {
+    pub fn new(
+        bytes_per_second: Option<u64>,
+        ops_per_second: Option<u64>,
+        when_idle: bool,
+    ) -> Self {
+        let limits = Self::default();
+        limits.set_bytes_per_second(bytes_per_second);
+        limits.set_ops_per_second(ops_per_second);
+        limits.set_when_idle(when_idle);
+
+        limits
+    }
+
+    pub fn bytes_per_second(&self) -> Option<u64> {
+        match self.bytes_per_second.load(Ordering::SeqCst) {
+            0 => None,
+            rate => Some(rate),
+        }
+    }
+
+    pub fn set_bytes_per_second(&self, rate: Option<u64>) {
+        self.bytes_per_second
+            .store(rate.unwrap_or_default(), Ordering::SeqCst);
+    }
+
+    pub fn ops_per_second(&self) -> Option<u64> {
+        match self.ops_per_second.load(Ordering::SeqCst) {
+            0 => None,
+            rate => Some(rate),
+        }
+    }
+
+    pub fn set_ops_per_second(&self, rate: Option<u64>) {
+        self.ops_per_second
+            .store(rate.unwrap_or_default(), Ordering::SeqCst);
+    }
+
+    pub fn when_idle(&self) -> bool {
+        self.when_idle.load(Ordering::SeqCst)
+    }
+
+    /// Only lets new files start while the system looks idle, see [`Limits::is_idle`].
+    pub fn set_when_idle(&self, when_idle: bool) {
+        self.when_idle.store(when_idle, Ordering::SeqCst);
+    }
+
+    /// Waits until `bytes` more bytes may be read or written.
+    pub async fn transfer(&self, bytes: u64) {
+        if let Some(rate) = self.bytes_per_second() {
+            let wait = self.bytes.lock().take(bytes, rate);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Waits until one more operation may be performed.
+    pub async fn operation(&self) {
+        if let Some(rate) = self.ops_per_second() {
+            let wait = self.ops.lock().take(1, rate);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Whether new files may be started.
+    ///
+    /// When running only while idle, the system counts as busy if the load average is
+    /// high or a disk is busy. The job's own writes count towards disk utilization, so
+    /// a job alone on a disk pauses every now and then to let others through.
+    /// Without `/proc`, the system always counts as idle.
+    pub fn is_idle(&self) -> bool {
+        if self.when_idle().not() {
+            return true;
+        }
+
+        self.probe.lock().is_idle()
+    }
+}
+
+/// Token bucket allowing bursts of up to one second's worth of tokens.
+#[derive(Debug)]
+struct Bucket {
+    available: f64,
+    at: Instant,
+}
+
+impl Default for Bucket {
+    fn default() -> Self {
+        Self {
+            available: 0.0,
+            at: Instant::now(),
+        }
+    }
+}
+
+impl Bucket {
+    /// Takes `amount` tokens refilled at `rate` per second and returns how long the
+    /// caller has to wait until they are actually available.
+    fn take(&mut self, amount: u64, rate: u64) -> Duration {
+        let now = Instant::now();
+        let rate = rate as f64;
+        let refill = now.duration_since(self.at).as_secs_f64() * rate;
+
+        self.available = (self.available + refill).min(rate) - amount as f64;
+        self.at = now;
+
+        match self.available < 0.0 {
+            true => Duration::from_secs_f64(-self.available / rate),
+            false => Duration::ZERO,
+        }
+    }
+}
+
+/// Last state of the system as read from `/proc`.
+#[derive(Debug, Default)]
+struct IdleProbe {
+    checked: Option<Instant>,
+    idle: bool,
+    /// Milliseconds each disk has spent doing I/O, as of the last check.
+    io_ticks: HashMap<String, u64>,
+}
+
+impl IdleProbe {
+    fn is_idle(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = match self.checked {
+            Some(checked) if now.duration_since(checked) < PROBE_INTERVAL => return self.idle,
+            Some(checked) => Some(now.duration_since(checked)),
+            None => None,
+        };
+
+        let cpus = std::thread::available_parallelism().map_or(1, |cpus| cpus.get()) as f64;
+        let load_busy = load_average().is_some_and(|load| load > cpus * MAX_LOAD_PER_CPU);
+
+        let io_ticks = disk_io_ticks().unwrap_or_default();
+        let disk_busy = match elapsed {
+            // The first check has nothing to compare the counters with.
+            None => false,
+            Some(elapsed) => io_ticks.iter().any(|(disk, ticks)| {
+                let previous = self.io_ticks.get(disk).unwrap_or(ticks);
+                let busy = ticks.saturating_sub(*previous) as f64;
+
+                busy / elapsed.as_millis().max(1) as f64 > MAX_DISK_UTILIZATION
+            }),
+        };
+
+        self.checked = Some(now);
+        self.io_ticks = io_ticks;
+        self.idle = (load_busy || disk_busy).not();
+        self.idle
+    }
+}
+
+/// The 1-minute load average.
+fn load_average() -> Option<f64> {
+    let loadavg = std::fs::read_to_string("/proc/loadavg").ok()?;
+
+    loadavg.split_whitespace().next()?.parse().ok()
+}
+
+/// Time spent doing I/O by each block device, in milliseconds.
+fn disk_io_ticks() -> Option<HashMap<String, u64>> {
+    let diskstats = std::fs::read_to_string("/proc/diskstats").ok()?;
+
+    let ticks = diskstats
+        .lines()
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let name = *fields.get(2)?;
+            if name.starts_with("loop") || name.starts_with("ram") {
+                return None;
+            }
+
+            Some((name.to_string(), fields.get(12)?.parse().ok()?))
+        })
+        .collect();
+
+    Some(ticks)
+}
+
+/// Applies [`Limits`] to the operations of another backend.
+#[derive(Debug)]
+pub struct Throttled {
+    inner: Arc<dyn IoBackend>,
+    limits: Arc<Limits>,
+}
+
+impl Throttled {
+    pub fn new(inner: Arc<dyn IoBackend>, limits: Arc<Limits>) -> Self {
+        Self { inner, limits }
+    }
+}
+
+#[async_trait]
+impl IoBackend for Throttled {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.limits.operation().await;
+        self.inner.rename(from, to).await
+    }
+
+    async fn read_at(&self, path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.limits.operation().await;
+        self.limits.transfer(len as u64).await;
+        self.inner.read_at(path, offset, len).await
+    }
+
+    async fn write(&self, path: &Path, content: Vec<u8>) -> io::Result<()> {
+        self.limits.operation().await;
+        self.limits.transfer(content.len() as u64).await;
+        self.inner.write(path, content).await
+    }
+
+    async fn write_at(&self, path: &Path, offset: u64, content: Vec<u8>) -> io::Result<()> {
+        self.limits.operation().await;
+        self.limits.transfer(content.len() as u64).await;
+        self.inner.write_at(path, offset, content).await
+    }
+
+    async fn fill(
+        &self,
+        path: &Path,
+        offset: u64,
+        len: u64,
+        fill: Fill,
+        progress: &Progress<'_>,
+    ) -> io::Result<()> {
+        self.limits.operation().await;
+
+        let end = offset + len;
+        let mut position = offset;
+        while position < end {
+            // Without a byte limit the whole range goes to the backend at once.
+            let chunk = match self.limits.bytes_per_second() {
+                None => end - position,
+                Some(rate) => (rate / 10).clamp(4096, THROTTLE_CHUNK).min(end - position),
+            };
+
+            self.limits.transfer(chunk).await;
+            self.inner
+                .fill(path, position, chunk, fill, progress)
+                .await?;
+            position += chunk;
+        }
+
+        Ok(())
+    }
+
+    async fn fsync(&self, path: &Path) -> io::Result<()> {
+        self.limits.operation().await;
+        self.inner.fsync(path).await
+    }
+
+    async fn remove(&self, path: &Path) -> io::Result<()> {
+        self.limits.operation().await;
+        self.inner.remove(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui::cleaner::io::TokioBackend;
+
+    #[test]
+    fn buckets_make_callers_wait_for_missing_tokens() {
+        let mut bucket = Bucket::default();
+
+        let first = bucket.take(50, 100);
+        let second = bucket.take(50, 100);
+
+        assert!(first > Duration::from_millis(450) && first <= Duration::from_millis(500));
+        assert!(second > Duration::from_millis(950) && second <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn unset_limits_are_unlimited() {
+        let limits = Limits::new(None, Some(10), false);
+
+        assert_eq!(limits.bytes_per_second(), None);
+        assert_eq!(limits.ops_per_second(), Some(10));
+        assert!(limits.is_idle());
+
+        limits.set_ops_per_second(None);
+        assert_eq!(limits.ops_per_second(), None);
+    }
+
+    #[tokio::test]
+    async fn reads_count_towards_the_byte_limit() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("file");
+        std::fs::write(&path, vec![1; 10_000]).unwrap();
+        let limits = Arc::new(Limits::new(Some(100_000), None, false));
+        let io = Throttled::new(Arc::new(TokioBackend), limits.clone());
+
+        let started = Instant::now();
+        let content = io.read_at(&path, 0, 10_000).await.unwrap();
+
+        assert_eq!(content.len(), 10_000);
+        assert!(started.elapsed() >= Duration::from_millis(90));
+
+        limits.set_bytes_per_second(None);
+        let started = Instant::now();
+        io.write(&path, vec![2; 10_000]).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(90));
+    }
+}
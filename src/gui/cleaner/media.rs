@@ -0,0 +1,1002 @@
+use crate::gui::cleaner::action::{replace, ActionContext, ActionOutcome, FileAction, FileEntry};
+use crate::gui::cleaner::blocking;
+use crate::gui::cleaner::io::Fill;
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use std::collections::BTreeSet;
+use std::ops::{Not, Range};
+use std::path::Path;
+
+/// Generator polynomial of the Ogg page checksum.
+const OGG_POLYNOMIAL: u32 = 0x04C1_1DB7;
+const OGG_CRC: [u32; 256] = ogg_crc_table();
+
+/// Bytes read to tell the format, enough for the first two frames of MPEG audio.
+const SNIFF_LEN: usize = 4096;
+
+/// Bit rates of MPEG audio frames in kbit/s by bit rate index, for MPEG-1 layers I, II
+/// and III, then MPEG-2 and 2.5 layer I, then layers II and III.
+const MPEG_BIT_RATES: [[u32; 14]; 5] = [
+    [
+        32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+    ],
+    [
+        32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+    ],
+    [
+        32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ],
+    [
+        32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+    ],
+    [8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+
+/// Sample rates of MPEG-1 audio frames in Hz, halved for MPEG-2 and quartered for 2.5.
+const MPEG_SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+/// Removes the tags of audio and video files without re-encoding them: ID3v1 and ID3v2
+/// tags, the Vorbis comments of Ogg Vorbis and Opus files, the comment, picture and
+/// application blocks of FLAC files, and the iTunes items and `©` atoms of MP4 files,
+/// GPS `©xyz` included.
+///
+/// The atoms of MP4 files are turned into `free` atoms of the same size, in place and
+/// reading only the `moov` atom, so the offsets of the media data stay valid. The other
+/// formats are written to a new file replacing the original. Files of other formats
+/// and files without tags are skipped. The kinds of tags removed go to the report as
+/// `removed`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripMediaTags;
+
+/// A media file without its tags.
+#[derive(Debug)]
+pub struct Stripped {
+    pub content: Vec<u8>,
+    /// Kinds of tags removed, `ID3v1`, `Vorbis comment` or `picture` for example.
+    pub removed: BTreeSet<String>,
+}
+
+#[async_trait]
+impl FileAction for StripMediaTags {
+    fn label(&self) -> &str {
+        "Untagged"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        let start = match ctx.io.read_at(&entry.path, 0, SNIFF_LEN).await {
+            Ok(start) => start,
+            Err(err) => return ActionOutcome::Failed(err.into()),
+        };
+
+        let removed = match is_mp4(&start, entry.len) {
+            true => match strip_mp4(ctx, &entry.path).await {
+                Ok(removed) => removed,
+                Err(err) => return ActionOutcome::Failed(err.context("reading the MP4 atoms")),
+            },
+            false if is_tagged(&start) => match self.rewrite(ctx, entry).await {
+                Ok(removed) => removed,
+                Err(err) => return ActionOutcome::Failed(err),
+            },
+            false => return ActionOutcome::Skipped,
+        };
+        if removed.is_empty() {
+            return ActionOutcome::Skipped;
+        }
+
+        let removed = removed.into_iter().collect::<Vec<_>>();
+        entry.details.insert("removed".to_string(), removed.into());
+        entry.advance(entry.len);
+
+        ActionOutcome::Applied
+    }
+}
+
+impl StripMediaTags {
+    /// Replaces the file with a copy without its tags and returns the kinds removed.
+    async fn rewrite(
+        &self,
+        ctx: &ActionContext,
+        entry: &FileEntry,
+    ) -> anyhow::Result<BTreeSet<String>> {
+        let content = ctx.io.read(&entry.path).await?;
+
+        // Ogg pages are checksummed again, which takes a while on long recordings.
+        let stripped = match blocking(move || strip(&content)).await? {
+            Some(stripped) if stripped.removed.is_empty().not() => stripped,
+            _ => return Ok(BTreeSet::new()),
+        };
+        replace(ctx, &entry.path, stripped.content).await?;
+
+        Ok(stripped.removed)
+    }
+}
+
+/// Whether `start` is the start of an MP4 file of `len` bytes, whose first atom is one
+/// of those found at the top level.
+fn is_mp4(start: &[u8], len: u64) -> bool {
+    match start.get(4..8) {
+        Some(b"ftyp") => true,
+        // Without `ftyp`, only a size that fits tells them from text.
+        Some(b"moov" | b"wide" | b"mdat" | b"free" | b"skip") => atom_header(start, 0, len).is_ok(),
+        _ => false,
+    }
+}
+
+/// Whether `start` is the start of a file [`strip`] handles.
+fn is_tagged(start: &[u8]) -> bool {
+    start.starts_with(b"ID3")
+        || start.starts_with(b"fLaC")
+        || start.starts_with(b"OggS")
+        || is_mpeg_audio(start)
+}
+
+/// `content` without its tags, or `None` if it is not a media file of a supported
+/// format. MP4 files are left to [`strip_mp4`].
+pub fn strip(content: &[u8]) -> anyhow::Result<Option<Stripped>> {
+    let mut removed = BTreeSet::new();
+
+    let start = id3v2_len(content).context("reading the ID3v2 tags")?;
+    if start > 0 {
+        removed.insert("ID3v2".to_string());
+    }
+    let media = &content[start..];
+
+    let content = if media.starts_with(b"fLaC") {
+        strip_flac(media, &mut removed).context("reading the FLAC blocks")?
+    } else if media.starts_with(b"OggS") {
+        strip_ogg(media, &mut removed).context("reading the Ogg pages")?
+    } else if start > 0 || is_mpeg_audio(media) {
+        strip_id3v1(media, &mut removed)
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(Stripped { content, removed }))
+}
+
+/// Whether `content` starts with two MPEG audio frames, the first one followed by the
+/// header of the second. A single sync word is not enough, the byte order mark of
+/// UTF-16LE text looks like one.
+fn is_mpeg_audio(content: &[u8]) -> bool {
+    match mpeg_frame_len(content) {
+        Some(len) => content.get(len..).and_then(mpeg_frame_len).is_some(),
+        None => false,
+    }
+}
+
+/// Length of the MPEG audio frame whose header starts `content`, or `None` if it does
+/// not start with a valid header.
+fn mpeg_frame_len(content: &[u8]) -> Option<usize> {
+    let header = match content.get(..3) {
+        Some(header) if header[0] == 0xFF && header[1] & 0xE0 == 0xE0 => header,
+        _ => return None,
+    };
+
+    // 3 for MPEG-1, 2 for MPEG-2, 0 for MPEG-2.5, and 3 for layer I down to 1 for III.
+    let version = (header[1] >> 3) & 0x03;
+    let layer = (header[1] >> 1) & 0x03;
+    let bit_rate = usize::from(header[2] >> 4);
+    let sample_rate = usize::from((header[2] >> 2) & 0x03);
+    if version == 1 || layer == 0 || bit_rate == 0 || bit_rate == 15 || sample_rate == 3 {
+        return None;
+    }
+
+    let rates = match (version, layer) {
+        (3, layer) => 3 - usize::from(layer),
+        (_, 3) => 3,
+        _ => 4,
+    };
+    let bit_rate = MPEG_BIT_RATES[rates][bit_rate - 1] * 1000;
+    let sample_rate = match version {
+        3 => MPEG_SAMPLE_RATES[sample_rate],
+        2 => MPEG_SAMPLE_RATES[sample_rate] / 2,
+        _ => MPEG_SAMPLE_RATES[sample_rate] / 4,
+    };
+    let padding = u32::from((header[2] >> 1) & 0x01);
+
+    let len = match (version, layer) {
+        (_, 3) => (12 * bit_rate / sample_rate + padding) * 4,
+        (3, _) | (_, 2) => 144 * bit_rate / sample_rate + padding,
+        _ => 72 * bit_rate / sample_rate + padding,
+    };
+
+    Some(len as usize)
+}
+
+/// Length of the ID3v2 tags at the start of `content`.
+fn id3v2_len(content: &[u8]) -> anyhow::Result<usize> {
+    let mut position = 0;
+
+    while content[position..].starts_with(b"ID3") {
+        let header = match content.get(position..position + 10) {
+            Some(header) => header,
+            None => bail!("truncated tag header at byte {position}"),
+        };
+        if header[6..].iter().any(|byte| byte & 0x80 != 0) {
+            bail!("invalid tag size at byte {position}");
+        }
+
+        // Sizes are "synchsafe", seven bits to a byte.
+        let size = header[6..]
+            .iter()
+            .fold(0, |size, byte| (size << 7) | usize::from(*byte));
+        let footer = match header[5] & 0x10 {
+            0 => 0,
+            _ => 10,
+        };
+        position += 10 + size + footer;
+        if position > content.len() {
+            bail!("the tag runs past the end of the file");
+        }
+    }
+
+    Ok(position)
+}
+
+/// MPEG audio without the ID3v1 tag at its end, and the extended tag before it.
+fn strip_id3v1(content: &[u8], removed: &mut BTreeSet<String>) -> Vec<u8> {
+    let mut end = content.len();
+
+    if end >= 128 && content[end - 128..].starts_with(b"TAG") {
+        end -= 128;
+        removed.insert("ID3v1".to_string());
+
+        if end >= 227 && content[end - 227..].starts_with(b"TAG+") {
+            end -= 227;
+        }
+    }
+
+    content[..end].to_vec()
+}
+
+/// Copies the metadata blocks but the comment, picture and application ones, and the
+/// audio frames as they are.
+fn strip_flac(content: &[u8], removed: &mut BTreeSet<String>) -> anyhow::Result<Vec<u8>> {
+    let mut blocks = Vec::new();
+    let mut position = 4;
+
+    loop {
+        let header = match content.get(position..position + 4) {
+            Some(header) => header,
+            None => bail!("truncated block header at byte {position}"),
+        };
+        let last = header[0] & 0x80 != 0;
+        let len =
+            (usize::from(header[1]) << 16) | (usize::from(header[2]) << 8) | usize::from(header[3]);
+        let block = match content.get(position..position + 4 + len) {
+            Some(block) => block,
+            None => bail!("the block at byte {position} runs past the end of the file"),
+        };
+
+        let tag = match header[0] & 0x7F {
+            2 => Some("application"),
+            4 => Some("Vorbis comment"),
+            6 => Some("picture"),
+            127 => bail!("invalid block type at byte {position}"),
+            _ => None,
+        };
+        match tag {
+            Some(tag) => {
+                removed.insert(tag.to_string());
+            }
+            None => blocks.push(block),
+        }
+        position += block.len();
+
+        if last {
+            break;
+        }
+    }
+
+    let mut output = Vec::with_capacity(content.len());
+    output.extend_from_slice(b"fLaC");
+    for (index, block) in blocks.iter().enumerate() {
+        let last = match index + 1 == blocks.len() {
+            true => 0x80,
+            false => 0,
+        };
+        output.push((block[0] & 0x7F) | last);
+        output.extend_from_slice(&block[1..]);
+    }
+    output.extend_from_slice(&content[position..]);
+
+    Ok(output)
+}
+
+/// A page of an Ogg stream.
+#[derive(Debug)]
+struct Page<'a> {
+    /// The whole page, header included.
+    bytes: &'a [u8],
+    serial: u32,
+    lacing: &'a [u8],
+    data: &'a [u8],
+}
+
+fn ogg_pages(content: &[u8]) -> anyhow::Result<Vec<Page<'_>>> {
+    let mut pages = Vec::new();
+    let mut position = 0;
+
+    while position < content.len() {
+        let header = match content.get(position..position + 27) {
+            Some(header) if header.starts_with(b"OggS") => header,
+            _ => bail!("expected a page at byte {position}"),
+        };
+        let lacing_end = position + 27 + usize::from(header[26]);
+        let lacing = match content.get(position + 27..lacing_end) {
+            Some(lacing) => lacing,
+            None => bail!("truncated page at byte {position}"),
+        };
+        let end = lacing_end + lacing.iter().map(|len| usize::from(*len)).sum::<usize>();
+        let data = match content.get(lacing_end..end) {
+            Some(data) => data,
+            None => bail!("truncated page at byte {position}"),
+        };
+
+        pages.push(Page {
+            bytes: &content[position..end],
+            serial: u32::from_le_bytes([header[14], header[15], header[16], header[17]]),
+            lacing,
+            data,
+        });
+        position = end;
+    }
+
+    Ok(pages)
+}
+
+/// Replaces the comment header of a Vorbis or Opus stream with one holding the vendor
+/// string only. The header pages are laid out again and the pages after them numbered
+/// and checksummed again.
+fn strip_ogg(content: &[u8], removed: &mut BTreeSet<String>) -> anyhow::Result<Vec<u8>> {
+    let pages = ogg_pages(content)?;
+    let serial = pages.first().map_or(0, |page| page.serial);
+    if pages.iter().any(|page| page.serial != serial) {
+        bail!("streams multiplexed together are not supported");
+    }
+
+    let headers = match pages.first().map(|page| page.data) {
+        Some(data) if data.starts_with(b"\x01vorbis") => 3,
+        Some(data) if data.starts_with(b"OpusHead") => 2,
+        // Other codecs keep their comments.
+        _ => return Ok(content.to_vec()),
+    };
+
+    // The headers are the first packets, the audio starts on a page of its own.
+    let mut packets = vec![Vec::new()];
+    let mut header_pages = 0;
+    for page in &pages {
+        let mut data = page.data;
+        for len in page.lacing {
+            let (segment, rest) = data.split_at(usize::from(*len));
+            data = rest;
+            if let Some(packet) = packets.last_mut() {
+                packet.extend_from_slice(segment);
+            }
+            if *len < 255 {
+                packets.push(Vec::new());
+            }
+        }
+        header_pages += 1;
+
+        if packets.len() > headers {
+            if packets.len() > headers + 1 || packets[headers].is_empty().not() {
+                bail!("the audio starts on the page of the last header");
+            }
+            packets.truncate(headers);
+            break;
+        }
+    }
+    if packets.len() != headers {
+        bail!("the stream ends within the headers");
+    }
+
+    let (prefix, framing) = match headers {
+        2 => (&b"OpusTags"[..], false),
+        _ => (&b"\x03vorbis"[..], true),
+    };
+    let comments = match packets.get(1) {
+        Some(comments) if comments.starts_with(prefix) => &comments[prefix.len()..],
+        _ => bail!("the second packet is not a comment header"),
+    };
+    let vendor_len = match comments.get(..4) {
+        Some(len) => u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize,
+        None => bail!("truncated comment header"),
+    };
+    let count = match comments.get(4 + vendor_len..8 + vendor_len) {
+        Some(count) => u32::from_le_bytes([count[0], count[1], count[2], count[3]]),
+        None => bail!("truncated comment header"),
+    };
+    if count == 0 {
+        return Ok(content.to_vec());
+    }
+    removed.insert("Vorbis comment".to_string());
+
+    let mut stripped = prefix.to_vec();
+    stripped.extend_from_slice(&comments[..4 + vendor_len]);
+    stripped.extend_from_slice(&0u32.to_le_bytes());
+    if framing {
+        stripped.push(1);
+    }
+    packets[1] = stripped;
+
+    // The identification header keeps its page, the others are laid out after it.
+    let mut output = Vec::with_capacity(content.len());
+    output.extend_from_slice(pages[0].bytes);
+    let mut sequence = 1;
+    write_ogg_packets(&mut output, &pages[0], &packets[1..], &mut sequence);
+    for page in &pages[header_pages..] {
+        let start = output.len();
+        output.extend_from_slice(page.bytes);
+        output[start + 18..start + 22].copy_from_slice(&sequence.to_le_bytes());
+        output[start + 22..start + 26].fill(0);
+        let crc = ogg_crc(&output[start..]);
+        output[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+        sequence += 1;
+    }
+
+    Ok(output)
+}
+
+/// Appends pages holding `packets`, numbered from `sequence`, with the stream of
+/// `first`.
+fn write_ogg_packets(output: &mut Vec<u8>, first: &Page, packets: &[Vec<u8>], sequence: &mut u32) {
+    // Lacing values, with whether each ends its packet.
+    let segments = packets
+        .iter()
+        .flat_map(|packet| {
+            let full = packet.len() / 255;
+            (0..=full).map(move |index| match index == full {
+                true => ((packet.len() % 255) as u8, true),
+                false => (255, false),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut data = packets.iter().flatten().copied();
+    let mut continued = false;
+    for chunk in segments.chunks(255) {
+        // Pages where no packet ends have no granule position.
+        let granule = match chunk.iter().any(|(_, ends)| *ends) {
+            true => 0u64,
+            false => u64::MAX,
+        };
+
+        let start = output.len();
+        output.extend_from_slice(b"OggS\0");
+        output.push(match continued {
+            true => 1,
+            false => 0,
+        });
+        output.extend_from_slice(&granule.to_le_bytes());
+        output.extend_from_slice(&first.bytes[14..18]);
+        output.extend_from_slice(&sequence.to_le_bytes());
+        output.extend_from_slice(&[0; 4]);
+        output.push(chunk.len() as u8);
+        output.extend(chunk.iter().map(|(len, _)| *len));
+        let len = chunk
+            .iter()
+            .map(|(len, _)| usize::from(*len))
+            .sum::<usize>();
+        output.extend(data.by_ref().take(len));
+
+        let crc = ogg_crc(&output[start..]);
+        output[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+        continued = chunk.last().is_some_and(|(_, ends)| ends.not());
+        *sequence += 1;
+    }
+}
+
+fn ogg_crc(page: &[u8]) -> u32 {
+    page.iter().fold(0, |crc, byte| {
+        (crc << 8) ^ OGG_CRC[usize::from((crc >> 24) as u8 ^ byte)]
+    })
+}
+
+const fn ogg_crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+
+    while index < 256 {
+        let mut crc = (index as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 0x8000_0000 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ OGG_POLYNOMIAL,
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+
+    table
+}
+
+/// An atom of an MP4 file.
+#[derive(Debug)]
+struct Atom {
+    kind: [u8; 4],
+    range: Range<usize>,
+    /// After the header.
+    body: Range<usize>,
+}
+
+impl Atom {
+    /// The type as written in the specifications, `©nam` for example.
+    fn name(&self) -> String {
+        self.kind.iter().map(|byte| char::from(*byte)).collect()
+    }
+}
+
+/// Type, header length and size of the atom whose header is at the start of `header`,
+/// at byte `position` of a parent with `room` bytes left.
+fn atom_header(header: &[u8], position: u64, room: u64) -> anyhow::Result<([u8; 4], u64, u64)> {
+    let (size, kind) = match header.get(..8) {
+        Some(header) => (
+            u32::from_be_bytes([header[0], header[1], header[2], header[3]]),
+            [header[4], header[5], header[6], header[7]],
+        ),
+        None => bail!("truncated atom header at byte {position}"),
+    };
+
+    let (header_len, size) = match size {
+        0 => (8, room),
+        1 => match header.get(8..16) {
+            Some(large) => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(large);
+                (16, u64::from_be_bytes(bytes))
+            }
+            None => bail!("truncated atom header at byte {position}"),
+        },
+        size => (8, u64::from(size)),
+    };
+    if size < header_len || size > room {
+        bail!("the atom at byte {position} runs past its parent");
+    }
+
+    Ok((kind, header_len, size))
+}
+
+/// The atoms within `range` of `content`.
+fn atoms(content: &[u8], range: Range<usize>) -> anyhow::Result<Vec<Atom>> {
+    let mut atoms = Vec::new();
+    let mut position = range.start;
+
+    while position + 8 <= range.end {
+        let room = (range.end - position) as u64;
+        let (kind, header_len, size) = atom_header(&content[position..], position as u64, room)?;
+        let (header_len, size) = (header_len as usize, size as usize);
+
+        atoms.push(Atom {
+            kind,
+            range: position..position + size,
+            body: position + header_len..position + size,
+        });
+        position += size;
+    }
+
+    Ok(atoms)
+}
+
+/// Collects the tags within `range`, whose parent is of type `parent`, into `tags`.
+fn find_tags(
+    content: &[u8],
+    range: Range<usize>,
+    parent: &[u8; 4],
+    tags: &mut Vec<Atom>,
+    removed: &mut BTreeSet<String>,
+) -> anyhow::Result<()> {
+    for atom in atoms(content, range)? {
+        match (parent, &atom.kind) {
+            (_, b"trak" | b"udta") => {
+                find_tags(content, atom.body.clone(), &atom.kind, tags, removed)?
+            }
+            (b"moov" | b"trak" | b"udta", b"meta") => {
+                // The ISO atom has a version and flags before its children, the
+                // QuickTime one does not.
+                let mut body = atom.body.clone();
+                if content.get(body.start + 8..body.start + 12) == Some(b"hdlr") {
+                    body.start += 4;
+                }
+                find_tags(content, body, &atom.kind, tags, removed)?
+            }
+            (b"meta", b"ilst") => {
+                let items = atoms(content, atom.body.clone())?;
+                if items.is_empty() {
+                    continue;
+                }
+                removed.extend(items.iter().map(Atom::name));
+                tags.push(atom);
+            }
+            (b"udta", [0xA9, ..]) => {
+                removed.insert(atom.name());
+                tags.push(atom);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Turns the tag atoms of the `moov` atoms of the MP4 file `path` into `free` atoms of
+/// the same size, their content zeroed, and returns the kinds removed.
+///
+/// Only the atom headers and the `moov` atoms are read, and nothing is written unless
+/// every `moov` atom could be read through.
+async fn strip_mp4(ctx: &ActionContext, path: &Path) -> anyhow::Result<BTreeSet<String>> {
+    let len = tokio::fs::metadata(path).await?.len();
+    let mut removed = BTreeSet::new();
+    let mut tags = Vec::new();
+    let mut position = 0;
+
+    while position < len {
+        let header = ctx.io.read_at(path, position, 16).await?;
+        let (kind, header_len, size) = atom_header(&header, position, len - position)?;
+
+        if &kind == b"moov" {
+            let moov = ctx.io.read_at(path, position, size as usize).await?;
+            if moov.len() as u64 != size {
+                bail!("the file was cut short while being read");
+            }
+
+            let mut found = Vec::new();
+            find_tags(
+                &moov,
+                header_len as usize..moov.len(),
+                b"moov",
+                &mut found,
+                &mut removed,
+            )?;
+            tags.extend(found.into_iter().map(|atom| (position, atom)));
+        }
+        position += size;
+    }
+
+    for (moov, atom) in tags {
+        let start = moov + atom.range.start as u64;
+        let body = moov + atom.body.start as u64;
+        ctx.io.write_at(path, start + 4, b"free".to_vec()).await?;
+        ctx.io
+            .fill(path, body, atom.body.len() as u64, Fill::Zeros, &|_| {})
+            .await?;
+    }
+    if removed.is_empty().not() {
+        ctx.io.fsync(path).await?;
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui::cleaner::action::Counters;
+    use crate::gui::cleaner::io::TokioBackend;
+    use std::sync::Arc;
+
+    const AUDIO: &[u8] = b"\xFF\xF8audio frames";
+
+    fn context(root: &Path) -> ActionContext {
+        ActionContext {
+            root: root.to_path_buf(),
+            outputs: Default::default(),
+            io: Arc::new(TokioBackend),
+        }
+    }
+
+    fn block(kind: u8, data: &[u8]) -> Vec<u8> {
+        let mut block = vec![kind];
+        block.extend_from_slice(&(data.len() as u32).to_be_bytes()[1..]);
+        block.extend_from_slice(data);
+        block
+    }
+
+    /// A FLAC file whose last block is a padding one when `padded`.
+    fn flac(padded: bool) -> Vec<u8> {
+        let mut flac = b"fLaC".to_vec();
+        flac.extend(block(0, &[7; 34]));
+        flac.extend(block(4, b"\x06\0\0\0vendor\x01\0\0\0\x0A\0\0\0ARTIST=who"));
+        match padded {
+            true => {
+                flac.extend(block(6, b"picture"));
+                flac.extend(block(0x81, &[0; 8]));
+            }
+            false => flac.extend(block(0x86, b"picture")),
+        }
+        flac.extend_from_slice(AUDIO);
+        flac
+    }
+
+    fn page(flags: u8, sequence: u32, packets: &[&[u8]]) -> Vec<u8> {
+        let mut page = b"OggS\0".to_vec();
+        page.push(flags);
+        page.extend_from_slice(&0u64.to_le_bytes());
+        page.extend_from_slice(&7u32.to_le_bytes());
+        page.extend_from_slice(&sequence.to_le_bytes());
+        page.extend_from_slice(&[0; 4]);
+        page.push(packets.len() as u8);
+        page.extend(packets.iter().map(|packet| packet.len() as u8));
+        for packet in packets {
+            page.extend_from_slice(packet);
+        }
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        page
+    }
+
+    fn vorbis() -> Vec<u8> {
+        let mut vorbis = page(2, 0, &[b"\x01vorbis identification"]);
+        vorbis.extend(page(
+            0,
+            1,
+            &[
+                b"\x03vorbis\x06\0\0\0vendor\x01\0\0\0\x0A\0\0\0ARTIST=who\x01",
+                b"\x05vorbis setup",
+            ],
+        ));
+        vorbis.extend(page(0, 2, &[AUDIO]));
+        vorbis.extend(page(4, 3, &[b"last frames"]));
+        vorbis
+    }
+
+    fn atom(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut atom = (body.len() as u32 + 8).to_be_bytes().to_vec();
+        atom.extend_from_slice(kind);
+        atom.extend_from_slice(body);
+        atom
+    }
+
+    fn moov() -> Vec<u8> {
+        let mut meta = vec![0; 4];
+        meta.extend(atom(b"hdlr", &[0; 25]));
+        meta.extend(atom(b"ilst", &atom(b"\xA9ART", b"artist")));
+
+        let mut udta = atom(b"\xA9xyz", b"+48.85+002.29/");
+        udta.extend(atom(b"meta", &meta));
+
+        let mut moov = atom(b"mvhd", &[1; 100]);
+        moov.extend(atom(b"trak", &atom(b"tkhd", &[2; 84])));
+        moov.extend(atom(b"udta", &udta));
+        atom(b"moov", &moov)
+    }
+
+    fn mp4() -> Vec<u8> {
+        let mut mp4 = atom(b"ftyp", b"isom\0\0\0\0");
+        mp4.extend(atom(b"mdat", AUDIO));
+        mp4.extend(moov());
+        mp4
+    }
+
+    /// Two MPEG-1 layer III frames at 128 kbit/s and 44.1 kHz, 417 bytes each.
+    fn mpeg() -> Vec<u8> {
+        let mut frame = vec![0xFF, 0xFB, 0x90, 0x00];
+        frame.resize(417, 0x55);
+        frame.repeat(2)
+    }
+
+    fn removed(entry: &FileEntry) -> String {
+        entry.details["removed"].to_string()
+    }
+
+    #[test]
+    fn flac_blocks_are_cut_and_the_last_flag_moved() {
+        for padded in [false, true] {
+            let stripped = strip(&flac(padded)).unwrap().unwrap();
+
+            let mut expected = b"fLaC".to_vec();
+            expected.extend(block(
+                match padded {
+                    true => 0,
+                    false => 0x80,
+                },
+                &[7; 34],
+            ));
+            if padded {
+                expected.extend(block(0x81, &[0; 8]));
+            }
+            expected.extend_from_slice(AUDIO);
+            assert_eq!(stripped.content, expected);
+            assert_eq!(
+                stripped.removed.into_iter().collect::<Vec<_>>(),
+                ["Vorbis comment", "picture"]
+            );
+        }
+    }
+
+    #[test]
+    fn ogg_checksum_matches_the_reference() {
+        // The check value of CRC-32/MPEG-2, which differs only by its initial value.
+        let mpeg2 = b"123456789".iter().fold(u32::MAX, |crc, byte| {
+            (crc << 8) ^ OGG_CRC[usize::from((crc >> 24) as u8 ^ byte)]
+        });
+        assert_eq!(mpeg2, 0x0376_E6E7);
+        assert_eq!(ogg_crc(b"123456789"), 0x89A1_897F);
+    }
+
+    #[test]
+    fn vorbis_comments_are_emptied_and_pages_renumbered() {
+        let stripped = strip(&vorbis()).unwrap().unwrap();
+        let pages = ogg_pages(&stripped.content).unwrap();
+
+        for (index, page) in pages.iter().enumerate() {
+            let mut bytes = page.bytes.to_vec();
+            bytes[22..26].fill(0);
+            assert_eq!(page.bytes[22..26], ogg_crc(&bytes).to_le_bytes());
+            assert_eq!(page.bytes[18..22], (index as u32).to_le_bytes());
+        }
+        assert_eq!(pages.len(), 4);
+        assert_eq!(pages[0].bytes, &vorbis()[..pages[0].bytes.len()]);
+        assert_eq!(
+            pages[1].data,
+            b"\x03vorbis\x06\0\0\0vendor\0\0\0\0\x01\x05vorbis setup"
+        );
+        assert_eq!(pages[1].lacing, [22, 13]);
+        assert_eq!(pages[2].data, AUDIO);
+        assert_eq!(pages[3].data, b"last frames");
+        assert_eq!(pages[3].bytes[5], 4);
+        assert_eq!(
+            stripped.removed.into_iter().collect::<Vec<_>>(),
+            ["Vorbis comment"]
+        );
+    }
+
+    #[test]
+    fn id3_tags_are_cut_around_the_frames() {
+        let mut mp3 = b"ID3\x04\0\0\0\0\0\x05title".to_vec();
+        mp3.extend_from_slice(AUDIO);
+        mp3.extend_from_slice(b"TAG");
+        mp3.extend_from_slice(&[0; 125]);
+
+        let stripped = strip(&mp3).unwrap().unwrap();
+
+        assert_eq!(stripped.content, AUDIO);
+        assert_eq!(
+            stripped.removed.into_iter().collect::<Vec<_>>(),
+            ["ID3v1", "ID3v2"]
+        );
+        assert!(strip(b"not media").unwrap().is_none());
+    }
+
+    #[test]
+    fn mpeg_audio_is_told_from_utf16_text() {
+        let mut mp3 = mpeg();
+        mp3.extend_from_slice(b"TAG");
+        mp3.extend_from_slice(&[0; 125]);
+        let stripped = strip(&mp3).unwrap().unwrap();
+        assert_eq!(stripped.content, mpeg());
+
+        // Layers II and I, MPEG-2 and 2.5.
+        assert_eq!(mpeg_frame_len(&[0xFF, 0xFD, 0x90]), Some(522));
+        assert_eq!(mpeg_frame_len(&[0xFF, 0xFF, 0x90]), Some(78 * 4));
+        assert_eq!(mpeg_frame_len(&[0xFF, 0xF3, 0x90]), Some(261));
+        assert_eq!(mpeg_frame_len(&[0xFF, 0xE3, 0x92]), Some(523));
+        assert_eq!(mpeg_frame_len(&[0xFF, 0xEB, 0x90]), None);
+        assert_eq!(mpeg_frame_len(&[0xFF, 0xF9, 0x90]), None);
+        assert_eq!(mpeg_frame_len(&[0xFF, 0xFB, 0xF0]), None);
+
+        let mut text = b"\xFF\xFE".to_vec();
+        text.extend("H\0i\0\n\0".as_bytes().repeat(100));
+        text.extend_from_slice(b"TAG");
+        text.extend_from_slice(&[0; 125]);
+        assert!(strip(&text).unwrap().is_none());
+        assert!(strip(&mpeg()[..419]).unwrap().is_none());
+    }
+
+    #[test]
+    fn malformed_media_fails_without_panicking() {
+        let moov = moov();
+        let files = [flac(false), flac(true), vorbis(), mp4(), mpeg()];
+
+        for file in &files {
+            for len in 0..file.len() {
+                let _ = strip(&file[..len]);
+            }
+        }
+        for len in 8..moov.len() {
+            let (mut tags, mut removed) = (Vec::new(), BTreeSet::new());
+            let _ = find_tags(&moov[..len], 8..len, b"moov", &mut tags, &mut removed);
+        }
+        assert!(strip(&flac(false)[..30]).is_err());
+        assert!(strip(&vorbis()[..40]).is_err());
+        assert!(strip(b"ID3\x04\0\0\0\0\x01\0").is_err());
+    }
+
+    #[tokio::test]
+    async fn mp4_tags_are_freed_in_place() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        let mut bare = moov();
+        bare.extend(atom(b"mdat", AUDIO));
+        let files = [("video.mp4", mp4()), ("bare.mov", bare)];
+
+        for (name, content) in &files {
+            let path = root.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            let counters = Arc::new(Counters::new(1));
+            let mut entry = FileEntry::new(0, path.clone(), content.len() as u64, counters);
+
+            let outcome = StripMediaTags.apply(&ctx, &mut entry).await;
+
+            assert!(matches!(outcome, ActionOutcome::Applied));
+            assert!(removed(&entry).contains("©xyz"));
+            assert!(removed(&entry).contains("©ART"));
+            let stripped = std::fs::read(&path).unwrap();
+            assert_eq!(stripped.len(), content.len());
+            assert!(stripped
+                .windows(AUDIO.len() + 8)
+                .any(|window| window == atom(b"mdat", AUDIO)));
+
+            // The GPS atom and the item list, 14 bytes each, are now free atoms of zeros.
+            let offset = |kind: &[u8]| {
+                content
+                    .windows(4)
+                    .position(|window| window == kind)
+                    .unwrap()
+            };
+            for kind in [&b"\xA9xyz"[..], b"ilst"] {
+                let at = offset(kind);
+                assert_eq!(&stripped[at..at + 4], b"free");
+                assert!(stripped[at + 4..at + 18].iter().all(|byte| *byte == 0));
+            }
+            let mvhd = offset(b"mvhd");
+            assert_eq!(stripped[mvhd..mvhd + 104], content[mvhd..mvhd + 104]);
+        }
+        assert_eq!(std::fs::read_dir(root.path()).unwrap().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn malformed_mp4_and_other_files_are_left_alone() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        let mut broken = mp4();
+        let moov = broken.len() - moov().len();
+        // The `mvhd` atom claims to run past the `moov` one.
+        broken[moov + 8..moov + 12].copy_from_slice(&1000u32.to_be_bytes());
+        let files = [
+            ("broken.mp4", broken),
+            ("notes.txt", b"ask free advice".to_vec()),
+            (
+                "utf16.txt",
+                [&b"\xFF\xFEH\0i\0"[..], b"TAG", &[0; 125]].concat(),
+            ),
+            ("short.txt", b"text".to_vec()),
+        ];
+
+        let mut outcomes = Vec::new();
+        for (name, content) in &files {
+            let path = root.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            let counters = Arc::new(Counters::new(1));
+            let mut entry = FileEntry::new(0, path.clone(), content.len() as u64, counters);
+            outcomes.push(StripMediaTags.apply(&ctx, &mut entry).await);
+            assert_eq!(&std::fs::read(&path).unwrap(), content);
+        }
+
+        assert!(matches!(outcomes[0], ActionOutcome::Failed(_)));
+        assert!(matches!(outcomes[1], ActionOutcome::Skipped));
+        assert!(matches!(outcomes[2], ActionOutcome::Skipped));
+        assert!(matches!(outcomes[3], ActionOutcome::Skipped));
+    }
+
+    #[tokio::test]
+    async fn tagged_audio_is_replaced() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        let path = root.path().join("song.flac");
+        std::fs::write(&path, flac(true)).unwrap();
+        let mut entry = FileEntry::new(0, path.clone(), 0, Arc::new(Counters::new(1)));
+
+        let outcome = StripMediaTags.apply(&ctx, &mut entry).await;
+
+        assert!(matches!(outcome, ActionOutcome::Applied));
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            strip(&flac(true)).unwrap().unwrap().content
+        );
+        assert_eq!(std::fs::read_dir(root.path()).unwrap().count(), 1);
+    }
+}
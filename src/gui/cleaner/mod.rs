@@ -0,0 +1,831 @@
+use crate::gui::cleaner::action::{
+    ActionContext, Counters, Delete, FileEntry, Pipeline, Rename, Truncate,
+};
+use crate::gui::cleaner::busy::{BusyCheck, Verdict};
+use crate::gui::cleaner::dirs::DirectoryRenamer;
+use crate::gui::cleaner::document::StripDocumentMetadata;
+use crate::gui::cleaner::image::StripImageMetadata;
+use crate::gui::cleaner::io::{IoBackend, TokioBackend};
+use crate::gui::cleaner::limit::{Limits, Throttled};
+use crate::gui::cleaner::media::StripMediaTags;
+use crate::gui::cleaner::report::{FileOutcome, FileReport, Report};
+use crate::gui::cleaner::source::{DirectorySource, Enumeration, FileSource};
+use iced_native::{subscription, Subscription};
+use parking_lot::Mutex;
+use std::hash::{Hash, Hasher};
+use std::ops::Not;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinSet;
+use tokio::time::{self, MissedTickBehavior};
+
+pub mod action;
+pub mod busy;
+pub mod compress;
+pub mod dirs;
+pub mod discover;
+pub mod document;
+pub mod image;
+pub mod io;
+pub mod limit;
+pub mod media;
+pub mod organize;
+pub mod pattern;
+pub mod pseudonym;
+pub mod redact;
+pub mod report;
+pub mod sanitize;
+pub mod source;
+pub mod tail;
+
+const PROGRESS_BUFFER: usize = 64;
+
+/// How often a running job reports its progress.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+pub fn clear_folder(process: ClearProcess) -> Subscription<Progress> {
+    subscription::unfold(
+        process.clone(),
+        State::Ready(Box::new(process)),
+        clearing_process,
+    )
+}
+
+async fn clearing_process(state: State) -> (Option<Progress>, State) {
+    match state {
+        State::Ready(process) => {
+            let (sender, receiver) = mpsc::channel(PROGRESS_BUFFER);
+            tokio::spawn(run(*process, sender));
+
+            (Some(Progress::Started), State::Process(receiver))
+        }
+        State::Process(mut receiver) => match receiver.recv().await {
+            Some(progress) => (Some(progress), State::Process(receiver)),
+            None => (None, State::Finished),
+        },
+        State::Finished => iced::futures::future::pending().await,
+    }
+}
+
+/// Runs a job to completion, processing up to `process.concurrency` files at once.
+///
+/// Progress is reported every [`PROGRESS_INTERVAL`] rather than per file, so that large
+/// jobs of small files do not flood the UI with redraws.
+async fn run(process: ClearProcess, sender: mpsc::Sender<Progress>) {
+    let context = ActionContext {
+        root: process.path.clone(),
+        outputs: Default::default(),
+        io: Arc::new(Throttled::new(process.io.clone(), process.limits.clone())),
+    };
+    let mut files = Enumeration::start(
+        process.source.clone(),
+        process.path.clone(),
+        process.save_scan.clone(),
+        context.outputs.clone(),
+        process
+            .side_files
+            .iter()
+            .chain(&process.report)
+            .cloned()
+            .collect(),
+    );
+    let counters = Arc::new(Counters::new(process.pipeline.len()));
+    let mut throughput = Throughput::new();
+    let worker = Arc::new(Worker {
+        pipeline: process.pipeline.clone(),
+        context,
+        counters: counters.clone(),
+        busy: process.busy.clone(),
+        report: process
+            .report
+            .as_ref()
+            .map(|_| Mutex::new(Report::new(process.path.clone()))),
+    });
+
+    // Directories are listed before renaming files touches their times.
+    let renaming = match process.directories {
+        None => None,
+        Some(renamer) => {
+            let root = process.path.clone();
+            Some(blocking(move || renamer.plan(&root)).await)
+        }
+    };
+
+    let mut ticker = time::interval(PROGRESS_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut tasks = JoinSet::new();
+    let mut deferred: Vec<(time::Instant, Queued)> = Vec::new();
+    let mut index = 0;
+    let mut enumerated = false;
+
+    let outcome = loop {
+        let canceled = process.cancel.notified();
+        if process.is_canceled() {
+            break Progress::Canceled;
+        }
+        if enumerated && tasks.is_empty() && deferred.is_empty() {
+            break Progress::Finished;
+        }
+
+        let ready = tasks.len() < process.concurrency && process.limits.is_idle();
+        let retry_at = deferred.iter().map(|(at, _)| *at).min();
+        let retry = time::sleep_until(retry_at.unwrap_or_else(time::Instant::now));
+
+        tokio::select! {
+            _ = canceled => break Progress::Canceled,
+            file = files.next(), if enumerated.not() && ready => {
+                match file {
+                    None => enumerated = true,
+                    Some(Err(_)) => break Progress::Errored,
+                    Some(Ok(path)) => {
+                        let file = Queued {
+                            index,
+                            path,
+                            deferrals: 0,
+                        };
+
+                        tasks.spawn(worker.clone().process(file));
+                        index += 1;
+                    }
+                }
+            }
+            _ = retry, if retry_at.is_some() && ready => {
+                let now = time::Instant::now();
+
+                if let Some(i) = deferred.iter().position(|(at, _)| *at <= now) {
+                    let (_, file) = deferred.swap_remove(i);
+                    tasks.spawn(worker.clone().process(file));
+                }
+            }
+            Some(result) = tasks.join_next(), if tasks.is_empty().not() => {
+                if let Ok(Some((file, wait))) = result {
+                    deferred.push((time::Instant::now() + wait, file));
+                }
+            }
+            _ = ticker.tick() => {
+                let progress = Progress::Advanced(Snapshot {
+                    completed: counters.snapshot(),
+                    skipped: counters.skipped(),
+                    discovered: files.discovered(),
+                    total: files.total(),
+                    discovered_bytes: files.discovered_bytes(),
+                    total_bytes: files.total_bytes(),
+                    bytes: counters.bytes(),
+                    compression: counters.compression(),
+                    current: counters.current(),
+                    throughput: throughput.update(counters.bytes()),
+                });
+
+                // A full channel means the UI is behind; the next batch supersedes this one.
+                if let Err(TrySendError::Closed(_)) = sender.try_send(progress) {
+                    // The subscription is gone, nobody is watching the job anymore.
+                    process.cancel();
+                }
+            }
+        }
+    };
+
+    // Files already handed to a worker are finished rather than left half-processed.
+    let bytes = files.discovered_bytes();
+    drop(files);
+    while tasks.join_next().await.is_some() {}
+
+    if matches!(outcome, Progress::Finished) && process.remove_empty {
+        let root = process.path.clone();
+        // Directories that cannot be listed are left in place, like those that cannot
+        // be removed.
+        let _ = blocking(move || dirs::remove_empty(&root)).await;
+    }
+
+    let outcome = match (outcome, renaming) {
+        (Progress::Finished, Some(Ok(renaming))) => {
+            let renames = blocking(move || renaming.run()).await;
+            if let Some(report) = &worker.report {
+                report.lock().directories = renames;
+            }
+            Progress::Finished
+        }
+        (Progress::Finished, Some(Err(_))) => Progress::Errored,
+        (outcome, _) => outcome,
+    };
+
+    let saved = match (&process.report, &worker.report) {
+        (Some(path), Some(report)) => report.lock().save(path).is_ok(),
+        _ => true,
+    };
+    let outcome = match saved {
+        true => outcome,
+        false => Progress::Errored,
+    };
+
+    let _ = sender
+        .send(Progress::Advanced(Snapshot {
+            completed: counters.snapshot(),
+            skipped: counters.skipped(),
+            discovered: index,
+            total: enumerated.then_some(index),
+            discovered_bytes: bytes,
+            total_bytes: enumerated.then_some(bytes),
+            bytes: counters.bytes(),
+            compression: counters.compression(),
+            current: None,
+            throughput: throughput.update(counters.bytes()),
+        }))
+        .await;
+    let _ = sender.send(outcome).await;
+}
+
+/// Runs `f` on the blocking thread pool, forwarding its panics.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
+
+/// A file waiting for a worker.
+#[derive(Debug)]
+struct Queued {
+    index: usize,
+    path: PathBuf,
+    /// Number of times the file was put back because it was still being written.
+    deferrals: usize,
+}
+
+/// Everything the workers of a job share.
+#[derive(Debug)]
+struct Worker {
+    pipeline: Pipeline,
+    context: ActionContext,
+    counters: Arc<Counters>,
+    busy: Arc<BusyCheck>,
+    report: Option<Mutex<Report>>,
+}
+
+impl Worker {
+    /// Runs the pipeline on `file`, or hands the file back together with how long to
+    /// wait before trying it again.
+    async fn process(self: Arc<Self>, file: Queued) -> Option<(Queued, Duration)> {
+        let metadata = match tokio::fs::metadata(&file.path).await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => return None,
+        };
+        let len = metadata.len();
+
+        let busy = self.busy.clone();
+        let deferrals = file.deferrals;
+        let verdict =
+            match tokio::task::spawn_blocking(move || busy.check(&metadata, deferrals)).await {
+                Ok(verdict) => verdict,
+                Err(err) => std::panic::resume_unwind(err.into_panic()),
+            };
+
+        let (outcome, details) = match verdict {
+            Verdict::Defer(wait) => {
+                let file = Queued {
+                    deferrals: file.deferrals + 1,
+                    ..file
+                };
+                return Some((file, wait));
+            }
+            Verdict::Skip(reason) => {
+                self.counters.skip(len);
+                (FileOutcome::Skipped { reason }, Default::default())
+            }
+            Verdict::Ready => {
+                let mut entry =
+                    FileEntry::new(file.index, file.path.clone(), len, self.counters.clone());
+
+                self.counters.start(&entry.path);
+                let outcome = self
+                    .pipeline
+                    .run(&self.context, &mut entry, &self.counters)
+                    .await;
+                self.counters.finish(&entry);
+                (outcome, entry.details)
+            }
+        };
+
+        if let Some(report) = &self.report {
+            report.lock().files.push(FileReport {
+                path: file.path,
+                outcome,
+                details,
+            });
+        }
+
+        None
+    }
+}
+
+/// Bytes per second, smoothed over the recent progress reports.
+struct Throughput {
+    bytes: u64,
+    at: Instant,
+    rate: Option<f64>,
+}
+
+impl Throughput {
+    /// Weight of the latest interval in the smoothed rate.
+    const SMOOTHING: f64 = 0.3;
+
+    fn new() -> Self {
+        Self {
+            bytes: 0,
+            at: Instant::now(),
+            rate: None,
+        }
+    }
+
+    fn update(&mut self, bytes: u64) -> f64 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return self.rate.unwrap_or_default();
+        }
+
+        let latest = bytes.saturating_sub(self.bytes) as f64 / elapsed;
+        let rate = match self.rate {
+            None => latest,
+            Some(rate) => rate + Self::SMOOTHING * (latest - rate),
+        };
+
+        self.bytes = bytes;
+        self.at = now;
+        self.rate = Some(rate);
+        rate
+    }
+}
+
+/// Steps applied to every file of a job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Operation {
+    /// Rename to `File{index}.txt` or a pseudonym, keeping the content.
+    Rename,
+    /// Truncate to zero bytes, keeping the name.
+    Clear,
+    /// Rename, then truncate.
+    #[default]
+    RenameAndClear,
+    /// Rename, truncate, then remove the file.
+    RenameClearAndDelete,
+    /// Remove the EXIF, XMP and IPTC blocks of images, keeping name and pixels.
+    StripImageMetadata,
+    /// Remove the author, company and custom properties of Office documents and PDFs.
+    StripDocumentMetadata,
+    /// Remove the ID3 tags, Vorbis comments and MP4 metadata of audio and video files.
+    StripMediaTags,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 7] = [
+        Operation::Rename,
+        Operation::Clear,
+        Operation::RenameAndClear,
+        Operation::RenameClearAndDelete,
+        Operation::StripImageMetadata,
+        Operation::StripDocumentMetadata,
+        Operation::StripMediaTags,
+    ];
+
+    /// Steps of the operation, renaming files with `rename`.
+    pub fn pipeline(&self, rename: Rename) -> Pipeline {
+        match self {
+            Operation::Rename => Pipeline::new().then(rename),
+            Operation::Clear => Pipeline::new().then(Truncate),
+            Operation::RenameAndClear => Pipeline::new().then(rename).then(Truncate),
+            Operation::RenameClearAndDelete => {
+                Pipeline::new().then(rename).then(Truncate).then(Delete)
+            }
+            Operation::StripImageMetadata => Pipeline::new().then(StripImageMetadata),
+            Operation::StripDocumentMetadata => Pipeline::new().then(StripDocumentMetadata),
+            Operation::StripMediaTags => Pipeline::new().then(StripMediaTags),
+        }
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            Operation::Rename => "Rename only",
+            Operation::Clear => "Clear only",
+            Operation::RenameAndClear => "Rename and clear",
+            Operation::RenameClearAndDelete => "Rename, clear and delete",
+            Operation::StripImageMetadata => "Strip image metadata",
+            Operation::StripDocumentMetadata => "Strip document metadata",
+            Operation::StripMediaTags => "Strip media tags",
+        };
+
+        f.write_str(text)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClearProcess {
+    path: PathBuf,
+    pipeline: Pipeline,
+    source: Arc<dyn FileSource>,
+    save_scan: Option<PathBuf>,
+    side_files: Vec<PathBuf>,
+    concurrency: usize,
+    io: Arc<dyn IoBackend>,
+    limits: Arc<Limits>,
+    busy: Arc<BusyCheck>,
+    directories: Option<DirectoryRenamer>,
+    remove_empty: bool,
+    report: Option<PathBuf>,
+    canceled: Arc<AtomicBool>,
+    cancel: Arc<Notify>,
+}
+
+impl Hash for ClearProcess {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+        self.pipeline.labels().hash(state);
+        self.source.to_string().hash(state);
+        self.io.name().hash(state)
+    }
+}
+
+impl ClearProcess {
+    /// Creates a job processing the files directly inside `path`.
+    pub fn new(path: PathBuf, pipeline: Pipeline) -> Self {
+        Self {
+            source: Arc::new(DirectorySource::new(path.clone())),
+            path,
+            pipeline,
+            save_scan: None,
+            side_files: Vec::new(),
+            concurrency: 1,
+            io: Arc::new(TokioBackend),
+            limits: Default::default(),
+            busy: Default::default(),
+            directories: None,
+            remove_empty: false,
+            report: None,
+            canceled: Default::default(),
+            cancel: Default::default(),
+        }
+    }
+
+    pub fn pipeline(&self) -> &Pipeline {
+        &self.pipeline
+    }
+
+    /// Takes the files from `source` instead of the job folder.
+    pub fn with_source(self, source: Arc<dyn FileSource>) -> Self {
+        Self { source, ..self }
+    }
+
+    /// Records the files found by the job to `scan`, see [`source::ScanSource`].
+    pub fn with_saved_scan(self, scan: PathBuf) -> Self {
+        Self {
+            save_scan: Some(scan),
+            ..self
+        }
+    }
+
+    /// Never processes `side_files`, which the actions write to. The report and the
+    /// saved scan are left out without being listed here.
+    pub fn with_side_files(self, side_files: Vec<PathBuf>) -> Self {
+        Self { side_files, ..self }
+    }
+
+    /// Processes up to `concurrency` files at the same time.
+    pub fn with_concurrency(self, concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+            ..self
+        }
+    }
+
+    /// Performs the file I/O of the actions through `io` instead of `tokio::fs`.
+    pub fn with_io(self, io: Arc<dyn IoBackend>) -> Self {
+        Self { io, ..self }
+    }
+
+    /// Throttles the job with `limits`, which may be changed while it runs.
+    pub fn with_limits(self, limits: Arc<Limits>) -> Self {
+        Self { limits, ..self }
+    }
+
+    /// Leaves alone the files `busy` finds in use by other programs.
+    pub fn with_busy_check(self, busy: BusyCheck) -> Self {
+        Self {
+            busy: Arc::new(busy),
+            ..self
+        }
+    }
+
+    /// Renames the subdirectories of the job folder with `renamer` once every file
+    /// has been processed.
+    pub fn with_directory_renaming(self, renamer: DirectoryRenamer) -> Self {
+        Self {
+            directories: Some(renamer),
+            ..self
+        }
+    }
+
+    /// Removes the subdirectories of the job folder left empty once every file has been
+    /// processed, before they are renamed.
+    pub fn with_empty_directories_removed(self) -> Self {
+        Self {
+            remove_empty: true,
+            ..self
+        }
+    }
+
+    /// Writes what happened to every file to `report` once the job is over.
+    pub fn with_report(self, report: PathBuf) -> Self {
+        Self {
+            report: Some(report),
+            ..self
+        }
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.canceled.load(Ordering::SeqCst)
+    }
+
+    pub fn cancel(&self) {
+        self.canceled.store(true, Ordering::SeqCst);
+        self.cancel.notify_waiters()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Progress {
+    Started,
+    Advanced(Snapshot),
+    Canceled,
+    Finished,
+    Errored,
+}
+
+/// Cumulative progress of a job at the time it was reported.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    /// Number of files each pipeline action has been applied to, in pipeline order.
+    pub completed: Vec<usize>,
+    /// Number of files left untouched because other programs were using them.
+    pub skipped: usize,
+    /// Number of files found so far.
+    pub discovered: usize,
+    /// Number of files of the job, once enumeration is over.
+    pub total: Option<usize>,
+    /// Size of the files found so far, in bytes.
+    pub discovered_bytes: u64,
+    /// Size of the files of the job, once enumeration is over.
+    pub total_bytes: Option<u64>,
+    /// Bytes processed so far, including the progress within files still in flight.
+    pub bytes: u64,
+    /// Size of the files compressed so far and of their archives, in bytes.
+    pub compression: (u64, u64),
+    /// The file most recently handed to a worker.
+    pub current: Option<PathBuf>,
+    /// Bytes per second, smoothed over the recent reports.
+    pub throughput: f64,
+}
+
+impl Snapshot {
+    /// Estimated time left, once the size of the job is known.
+    pub fn eta(&self) -> Option<Duration> {
+        match self.total_bytes {
+            Some(total_bytes) if self.throughput > 0.0 => Some(Duration::from_secs_f64(
+                total_bytes.saturating_sub(self.bytes) as f64 / self.throughput,
+            )),
+            _ => None,
+        }
+    }
+
+    /// How many times smaller the compressed files got, if any were compressed.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        match self.compression {
+            (0, _) | (_, 0) => None,
+            (from, to) => Some(from as f64 / to as f64),
+        }
+    }
+
+    /// Bytes freed by compressing files.
+    pub fn bytes_saved(&self) -> u64 {
+        self.compression.0.saturating_sub(self.compression.1)
+    }
+
+    /// Share of the job already processed, between 0 and 1.
+    pub fn ratio(&self) -> f32 {
+        let total = self.total_bytes.unwrap_or(self.discovered_bytes);
+
+        match total {
+            0 => 0.0,
+            total => (self.bytes as f64 / total as f64).min(1.0) as f32,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum State {
+    Ready(Box<ClearProcess>),
+    Process(mpsc::Receiver<Progress>),
+    Finished,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operations_run_their_steps_in_order() {
+        let labels = |operation: Operation| operation.pipeline(Rename::default()).labels();
+
+        assert_eq!(labels(Operation::Rename), ["Renamed"]);
+        assert_eq!(labels(Operation::Clear), ["Cleared"]);
+        assert_eq!(labels(Operation::RenameAndClear), ["Renamed", "Cleared"]);
+        assert_eq!(
+            labels(Operation::RenameClearAndDelete),
+            ["Renamed", "Cleared", "Deleted"]
+        );
+    }
+
+    /// Runs `process` to completion and returns its last snapshot and outcome.
+    async fn finish(process: ClearProcess) -> (Snapshot, Progress) {
+        let (sender, mut receiver) = mpsc::channel(PROGRESS_BUFFER);
+        run(process, sender).await;
+
+        let mut snapshot = None;
+        let mut outcome = None;
+        while let Some(progress) = receiver.recv().await {
+            match progress {
+                Progress::Advanced(latest) => snapshot = Some(latest),
+                progress => outcome = Some(progress),
+            }
+        }
+        (snapshot.unwrap(), outcome.unwrap())
+    }
+
+    #[tokio::test]
+    async fn finished_jobs_report_their_total() {
+        let root = tempfile::tempdir().unwrap();
+        for name in ["a", "b", "c"] {
+            std::fs::write(root.path().join(name), "content").unwrap();
+        }
+        let process = ClearProcess::new(
+            root.path().to_path_buf(),
+            Operation::Clear.pipeline(Rename::default()),
+        );
+
+        let (snapshot, outcome) = finish(process).await;
+
+        assert!(matches!(outcome, Progress::Finished));
+        assert_eq!(snapshot.completed, [3]);
+        assert_eq!(snapshot.total, Some(3));
+        assert_eq!(snapshot.total_bytes, Some(3 * 7));
+        assert_eq!(std::fs::read(root.path().join("a")).unwrap(), b"");
+    }
+
+    #[tokio::test]
+    async fn parallel_workers_process_every_file_once() {
+        let root = tempfile::tempdir().unwrap();
+        for i in 0..32 {
+            std::fs::write(root.path().join(format!("{i}.log")), "content").unwrap();
+        }
+        let process = ClearProcess::new(
+            root.path().to_path_buf(),
+            Operation::Rename.pipeline(Rename::default()),
+        )
+        .with_concurrency(4);
+
+        let (snapshot, _) = finish(process).await;
+
+        let mut names: Vec<_> = std::fs::read_dir(root.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        let mut expected: Vec<_> = (0..32).map(|i| format!("File{i}.txt")).collect();
+        expected.sort();
+
+        assert_eq!(snapshot.completed, [32]);
+        assert_eq!(names, expected);
+    }
+
+    #[tokio::test]
+    async fn flattening_moves_every_file_to_the_job_folder() {
+        let root = tempfile::tempdir().unwrap();
+        let data = tempfile::tempdir().unwrap();
+        let path = root.path().to_path_buf();
+        std::fs::create_dir_all(path.join("a/b")).unwrap();
+        std::fs::write(path.join("a/b/deep.txt"), "deep").unwrap();
+        std::fs::write(path.join("top.txt"), "top").unwrap();
+        let manifest = data.path().join("FLATTENED");
+        let pipeline = Pipeline::new().then(Rename::default().with_flattening(manifest.clone()));
+        let process = ClearProcess::new(path.clone(), pipeline)
+            .with_source(Arc::new(DirectorySource::recursive(path.clone())))
+            .with_empty_directories_removed();
+
+        let (snapshot, _) = finish(process).await;
+
+        let mut names: Vec<_> = std::fs::read_dir(&path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(snapshot.completed, [2]);
+        assert_eq!(names, ["File0.txt", "File1.txt"]);
+
+        let manifest = std::fs::read_to_string(manifest).unwrap();
+        let mut lines: Vec<_> = manifest.lines().collect();
+        lines.sort();
+        let moved = |from: &str| {
+            let to = lines
+                .iter()
+                .find_map(|line| line.strip_prefix(&format!("{from}\t")));
+            std::fs::read_to_string(path.join(to.unwrap())).unwrap()
+        };
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            moved(
+                &std::path::Path::new("a")
+                    .join("b")
+                    .join("deep.txt")
+                    .display()
+                    .to_string()
+            ),
+            "deep"
+        );
+        assert_eq!(moved("top.txt"), "top");
+    }
+
+    #[tokio::test]
+    async fn canceled_jobs_leave_the_total_unknown() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("a"), "content").unwrap();
+        let process = ClearProcess::new(
+            root.path().to_path_buf(),
+            Operation::Clear.pipeline(Rename::default()),
+        );
+        process.cancel();
+
+        let (snapshot, outcome) = finish(process).await;
+
+        assert!(matches!(outcome, Progress::Canceled));
+        assert_eq!(snapshot.total, None);
+        assert_eq!(snapshot.total_bytes, None);
+        assert_eq!(std::fs::read(root.path().join("a")).unwrap(), b"content");
+    }
+
+    #[test]
+    fn throughput_is_smoothed_over_reports() {
+        let mut throughput = Throughput::new();
+        throughput.at -= Duration::from_secs(1);
+        let first = throughput.update(1000);
+        assert!((900.0..=1000.0).contains(&first), "{first}");
+
+        throughput.at -= Duration::from_secs(1);
+        let second = throughput.update(1000);
+        let expected = first * (1.0 - Throughput::SMOOTHING);
+        assert!((second - expected).abs() < 10.0, "{second}");
+    }
+
+    #[test]
+    fn snapshots_estimate_the_time_left() {
+        let snapshot = Snapshot {
+            discovered_bytes: 2000,
+            bytes: 500,
+            throughput: 100.0,
+            ..Default::default()
+        };
+        assert_eq!(snapshot.eta(), None);
+        assert_eq!(snapshot.ratio(), 0.25);
+
+        let snapshot = Snapshot {
+            total_bytes: Some(1000),
+            ..snapshot
+        };
+        assert_eq!(snapshot.eta(), Some(Duration::from_secs(5)));
+        assert_eq!(snapshot.ratio(), 0.5);
+
+        let snapshot = Snapshot {
+            bytes: 1500,
+            ..snapshot
+        };
+        assert_eq!(snapshot.eta(), Some(Duration::ZERO));
+        assert_eq!(snapshot.ratio(), 1.0);
+        assert_eq!(Snapshot::default().ratio(), 0.0);
+    }
+
+    #[test]
+    fn operations_have_distinct_names() {
+        let names: std::collections::HashSet<_> = Operation::ALL
+            .iter()
+            .map(|operation| operation.to_string())
+            .collect();
+
+        assert_eq!(names.len(), Operation::ALL.len());
+        assert_eq!(Operation::default(), Operation::RenameAndClear);
+    }
+}
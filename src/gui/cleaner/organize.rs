@@ -0,0 +1,249 @@
+use crate::gui::cleaner::action::{move_to, ActionContext, ActionOutcome, FileAction, FileEntry};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Upper bounds of the size buckets, with the name of their folder.
+const SIZE_BUCKETS: [(u64, &str); 4] = [
+    (1 << 10, "0-1K"),
+    (1 << 20, "1K-1M"),
+    (100 << 20, "1M-100M"),
+    (1 << 30, "100M-1G"),
+];
+
+/// Moves the file into a subfolder of the job folder chosen by a [`SortRule`], keeping
+/// its name unless another file already has it there.
+#[derive(Debug, Clone, Copy)]
+pub struct Organize {
+    rule: SortRule,
+    dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortRule {
+    /// `pdf/`, with files without an extension in `other/`.
+    Extension,
+    /// `image/png/`, guessed from the extension.
+    Mime,
+    /// `2022/09/`, from the modification time in UTC.
+    Date,
+    /// `0-1K/`, `1K-1M/`, `1M-100M/`, `100M-1G/` or `1G+/`.
+    Size,
+}
+
+impl Organize {
+    pub fn new(rule: SortRule) -> Self {
+        Self {
+            rule,
+            dry_run: false,
+        }
+    }
+
+    /// Records where every file would go to its report as `planned`, with a path relative
+    /// to the job folder, instead of moving it. The actions after it do not run.
+    pub fn with_dry_run(self, dry_run: bool) -> Self {
+        Self { dry_run, ..self }
+    }
+
+    async fn folder(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        let folder = match self.rule {
+            SortRule::Extension => match path.extension() {
+                Some(extension) => PathBuf::from(extension.to_string_lossy().to_lowercase()),
+                None => PathBuf::from("other"),
+            },
+            SortRule::Mime => {
+                let mime = mime_guess::from_path(path).first_or_octet_stream();
+                [mime.type_().as_str(), mime.subtype().as_str()]
+                    .iter()
+                    .collect()
+            }
+            SortRule::Date => {
+                let modified = tokio::fs::metadata(path).await?.modified()?;
+                let (year, month, _) = utc_date(modified);
+                PathBuf::from(format!("{year:04}")).join(format!("{month:02}"))
+            }
+            SortRule::Size => {
+                let len = tokio::fs::metadata(path).await?.len();
+                let bucket = SIZE_BUCKETS
+                    .iter()
+                    .find(|(limit, _)| len < *limit)
+                    .map_or("1G+", |(_, name)| name);
+                PathBuf::from(bucket)
+            }
+        };
+
+        Ok(folder)
+    }
+}
+
+#[async_trait]
+impl FileAction for Organize {
+    fn label(&self) -> &str {
+        match self.dry_run {
+            true => "Planned",
+            false => "Organized",
+        }
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        let dir = match self.folder(&entry.path).await {
+            Ok(folder) => ctx.root.join(folder),
+            Err(err) => return ActionOutcome::Failed(err),
+        };
+        // Already sorted by an earlier run.
+        if entry.path.parent() == Some(dir.as_path()) {
+            return ActionOutcome::Skipped;
+        }
+
+        let stem = entry.path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = entry
+            .path
+            .extension()
+            .map(|extension| extension.to_string_lossy());
+        let new_path = ctx.outputs.claim(&dir, &stem, extension.as_deref());
+
+        // The path stays claimed, so that files planned for the same folder get
+        // different names.
+        if self.dry_run {
+            let to = new_path.strip_prefix(&ctx.root).unwrap_or(&new_path);
+            entry
+                .details
+                .insert("planned".to_string(), to.display().to_string().into());
+
+            return ActionOutcome::Stop;
+        }
+
+        if let Err(err) = tokio::fs::create_dir_all(&dir).await {
+            ctx.outputs.remove(&new_path);
+            return ActionOutcome::Failed(err.into());
+        }
+
+        move_to(ctx, entry, new_path).await.map(|_| ()).into()
+    }
+}
+
+/// Year, month and day of `time` in UTC.
+pub fn utc_date(time: SystemTime) -> (i64, u32, u32) {
+    let seconds = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs() as i64),
+    };
+
+    // Converts days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = seconds.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui::cleaner::action::Counters;
+    use crate::gui::cleaner::io::TokioBackend;
+    use std::ops::Not;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn context(root: &Path) -> ActionContext {
+        ActionContext {
+            root: root.to_path_buf(),
+            outputs: Default::default(),
+            io: Arc::new(TokioBackend),
+        }
+    }
+
+    fn entry(path: PathBuf) -> FileEntry {
+        FileEntry::new(0, path, 0, Arc::new(Counters::new(1)))
+    }
+
+    #[test]
+    fn dates_are_converted_to_utc() {
+        let at = |seconds: u64| utc_date(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds));
+        let before = |seconds: u64| utc_date(SystemTime::UNIX_EPOCH - Duration::from_secs(seconds));
+
+        assert_eq!(at(0), (1970, 1, 1));
+        assert_eq!(at(951_782_400), (2000, 2, 29));
+        assert_eq!(at(1_664_582_399), (2022, 9, 30));
+        assert_eq!(at(1_664_582_400), (2022, 10, 1));
+        assert_eq!(before(1), (1969, 12, 31));
+    }
+
+    #[tokio::test]
+    async fn rules_pick_the_folder() {
+        let root = tempfile::tempdir().unwrap();
+        let photo = root.path().join("Photo.JPG");
+        std::fs::write(&photo, vec![0; 2000]).unwrap();
+        let time = filetime::FileTime::from_unix_time(1_664_582_400, 0);
+        filetime::set_file_mtime(&photo, time).unwrap();
+
+        for (rule, folder) in [
+            (SortRule::Extension, "jpg"),
+            (SortRule::Mime, "image/jpeg"),
+            (SortRule::Date, "2022/10"),
+            (SortRule::Size, "1K-1M"),
+        ] {
+            let organize = Organize::new(rule);
+            assert_eq!(organize.folder(&photo).await.unwrap(), Path::new(folder));
+        }
+        let organize = Organize::new(SortRule::Extension);
+        let bare = organize.folder(Path::new("README")).await.unwrap();
+        assert_eq!(bare, Path::new("other"));
+    }
+
+    #[tokio::test]
+    async fn files_are_moved_into_their_folder() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        std::fs::create_dir(root.path().join("pdf")).unwrap();
+        std::fs::write(root.path().join("pdf/a.pdf"), "taken").unwrap();
+        std::fs::write(root.path().join("a.pdf"), "moved").unwrap();
+        let organize = Organize::new(SortRule::Extension);
+
+        let mut moved = entry(root.path().join("a.pdf"));
+        let outcome = organize.apply(&ctx, &mut moved).await;
+        assert!(matches!(outcome, ActionOutcome::Applied));
+        assert_eq!(moved.path, root.path().join("pdf/a(1).pdf"));
+        assert_eq!(std::fs::read(&moved.path).unwrap(), b"moved");
+
+        let outcome = organize.apply(&ctx, &mut moved).await;
+        assert!(matches!(outcome, ActionOutcome::Skipped));
+    }
+
+    #[tokio::test]
+    async fn dry_runs_record_the_plan_and_stop() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        let organize = Organize::new(SortRule::Extension).with_dry_run(true);
+
+        let mut planned = Vec::new();
+        for dir in ["a", "b"] {
+            let path = root.path().join(dir).join("notes.txt");
+            std::fs::create_dir(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, dir).unwrap();
+
+            let mut entry = entry(path.clone());
+            let outcome = organize.apply(&ctx, &mut entry).await;
+            assert!(matches!(outcome, ActionOutcome::Stop));
+            assert_eq!(entry.path, path);
+            planned.push(entry.details["planned"].clone());
+        }
+
+        let txt = |name: &str| Path::new("txt").join(name).display().to_string();
+        assert_eq!(planned, [txt("notes.txt"), txt("notes(1).txt")]);
+        assert!(root.path().join("txt").exists().not());
+    }
+}
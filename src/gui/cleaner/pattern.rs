@@ -0,0 +1,374 @@
+use crate::gui::cleaner::action::{candidates, same_file};
+use crate::gui::cleaner::organize::utc_date;
+use crate::gui::cleaner::source::FileSource;
+use regex::{Captures, Regex};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+/// Naming of files by find and replace, see [`super::action::Naming::Pattern`].
+///
+/// The replacement may refer to the groups of `find` as `$1`, `${name}`, ... and
+/// contain the tokens `{n}`, the position of the file in the job counted from `start`,
+/// `{n:W}`, the same padded with zeros to W digits, and `{date}`, the modification
+/// date as `YYYY-MM-DD`.
+#[derive(Debug, Clone, Default)]
+pub struct RenamePattern {
+    find: Option<(Regex, String)>,
+    case: Case,
+    transliterate: bool,
+    start: usize,
+}
+
+/// Case conversion of the name without its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Case {
+    #[default]
+    Keep,
+    Lower,
+    Upper,
+    /// Every word capitalized.
+    Title,
+}
+
+impl RenamePattern {
+    pub fn new() -> Self {
+        Self {
+            start: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Replaces every match of `find` in the name with `replace`.
+    pub fn with_replacement(self, find: Regex, replace: String) -> Self {
+        Self {
+            find: Some((find, replace)),
+            ..self
+        }
+    }
+
+    pub fn with_case(self, case: Case) -> Self {
+        Self { case, ..self }
+    }
+
+    /// Replaces non-ASCII characters with their closest ASCII spelling, `é` with `e`.
+    pub fn with_transliteration(self, transliterate: bool) -> Self {
+        Self {
+            transliterate,
+            ..self
+        }
+    }
+
+    /// Counts files from `start` in `{n}`, 1 by default.
+    pub fn with_counter_start(self, start: usize) -> Self {
+        Self { start, ..self }
+    }
+
+    /// New name of the file called `name`, the `index`th of the job.
+    pub fn rename(&self, name: &str, index: usize, modified: SystemTime) -> String {
+        let mut name = match &self.find {
+            None => name.to_string(),
+            Some((find, replace)) => {
+                let replace = self.expand(replace, index, modified);
+                find.replace_all(name, replace.as_str()).into_owned()
+            }
+        };
+
+        if self.transliterate {
+            name = unidecode::unidecode(&name);
+        }
+
+        let (stem, extension) = match name.rfind('.') {
+            Some(dot) if dot > 0 => name.split_at(dot),
+            _ => (name.as_str(), ""),
+        };
+        let stem = match self.case {
+            Case::Keep => stem.to_string(),
+            Case::Lower => stem.to_lowercase(),
+            Case::Upper => stem.to_uppercase(),
+            Case::Title => title_case(stem),
+        };
+
+        stem + extension
+    }
+
+    fn expand(&self, replace: &str, index: usize, modified: SystemTime) -> String {
+        static TOKENS: OnceLock<Regex> = OnceLock::new();
+        let tokens = TOKENS.get_or_init(|| {
+            Regex::new(r"\{(n(?::(\d+))?|date)\}").expect("the token pattern is valid")
+        });
+
+        tokens
+            .replace_all(replace, |captures: &Captures| {
+                if &captures[1] == "date" {
+                    let (year, month, day) = utc_date(modified);
+                    return format!("{year:04}-{month:02}-{day:02}");
+                }
+
+                let width = captures.get(2).map_or(0, |width| {
+                    width.as_str().parse::<usize>().unwrap_or_default()
+                });
+                format!("{:0width$}", self.start + index)
+            })
+            .into_owned()
+    }
+}
+
+fn title_case(text: &str) -> String {
+    let mut title = String::with_capacity(text.len());
+    let mut word_start = true;
+
+    for c in text.chars() {
+        match word_start {
+            true => title.extend(c.to_uppercase()),
+            false => title.extend(c.to_lowercase()),
+        }
+        word_start = c.is_alphanumeric().not();
+    }
+
+    title
+}
+
+/// Why the new name of a file cannot be used as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflict {
+    /// The new name is empty or contains a path separator.
+    Invalid,
+    /// Several files would get the same name.
+    Duplicate,
+    /// Another file already has the name.
+    Exists,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Conflict::Invalid => f.write_str("invalid name"),
+            Conflict::Duplicate => f.write_str("same name as another file"),
+            Conflict::Exists => f.write_str("name already taken"),
+        }
+    }
+}
+
+impl Conflict {
+    pub fn check(name: &str) -> Option<Conflict> {
+        match name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            true => Some(Conflict::Invalid),
+            false => None,
+        }
+    }
+}
+
+/// A file of a [`preview`].
+#[derive(Debug, Clone)]
+pub struct PreviewRow {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub conflict: Option<Conflict>,
+}
+
+/// Lists the names the files of `source` would get, without renaming anything.
+///
+/// The names are handed out like the job does with [`Outputs::claim`]: a name taken when
+/// the job starts stays taken, even by a file renamed away, so files in conflict get a
+/// `(1)`, `(2)`, ... suffix, or keep their name if it is invalid. Files of the job sharing
+/// a new name may get their suffixes in another order when processed in parallel.
+///
+/// [`Outputs::claim`]: super::action::Outputs::claim
+pub fn preview(
+    pattern: &RenamePattern,
+    source: &dyn FileSource,
+) -> anyhow::Result<Vec<PreviewRow>> {
+    let mut rows = Vec::new();
+    let mut targets = HashMap::<PathBuf, usize>::new();
+    let mut claimed = HashSet::new();
+
+    let files = source
+        .files()?
+        .filter_map(|path| match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => Some((path, metadata)),
+            _ => None,
+        });
+    for (index, (from, metadata)) in files.enumerate() {
+        let name = from.file_name().unwrap_or_default().to_string_lossy();
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        let new_name = pattern.rename(&name, index, modified);
+
+        if let Some(conflict) = Conflict::check(&new_name) {
+            let to = from.clone();
+            rows.push((
+                PathBuf::new(),
+                PreviewRow {
+                    from,
+                    to,
+                    conflict: Some(conflict),
+                },
+            ));
+            continue;
+        }
+
+        let target = from.with_file_name(&new_name);
+        if target == from {
+            let to = from.clone();
+            rows.push((
+                target,
+                PreviewRow {
+                    from,
+                    to,
+                    conflict: None,
+                },
+            ));
+            continue;
+        }
+
+        let to = match same_file(&from, &target) {
+            true => target.clone(),
+            false => {
+                let new_name = Path::new(&new_name);
+                let stem = new_name.file_stem().unwrap_or_default().to_string_lossy();
+                let extension = new_name
+                    .extension()
+                    .map(|extension| extension.to_string_lossy());
+                let dir = from.parent().unwrap_or(Path::new(""));
+                let to = candidates(dir, &stem, extension.as_deref())
+                    .find(|path| path.exists().not() && claimed.insert(path.clone()))
+                    .expect("there are infinitely many candidates");
+                to
+            }
+        };
+        *targets.entry(target.clone()).or_default() += 1;
+        rows.push((
+            target,
+            PreviewRow {
+                from,
+                to,
+                conflict: None,
+            },
+        ));
+    }
+
+    for (target, row) in rows.iter_mut().filter(|(_, row)| row.conflict.is_none()) {
+        if targets.get(target).is_some_and(|&count| count > 1) {
+            row.conflict = Some(Conflict::Duplicate);
+        } else if row.to != *target {
+            row.conflict = Some(Conflict::Exists);
+        }
+    }
+
+    Ok(rows.into_iter().map(|(_, row)| row).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui::cleaner::source::DirectorySource;
+
+    fn day(days: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(days * 86_400)
+    }
+
+    #[test]
+    fn replacements_expand_groups_and_tokens() {
+        let pattern = RenamePattern::new()
+            .with_replacement(
+                Regex::new(r"^IMG_(\d+)").unwrap(),
+                "{date} {n:3} $1".to_string(),
+            )
+            .with_counter_start(7);
+
+        assert_eq!(
+            pattern.rename("IMG_0042.jpg", 2, day(19_000)),
+            "2022-01-08 009 0042.jpg"
+        );
+        assert_eq!(pattern.rename("notes.txt", 0, day(0)), "notes.txt");
+    }
+
+    #[test]
+    fn case_and_transliteration_leave_the_extension() {
+        let pattern = RenamePattern::new()
+            .with_case(Case::Title)
+            .with_transliteration(true);
+        assert_eq!(
+            pattern.rename("crème brûlée-RECIPE.TXT", 0, day(0)),
+            "Creme Brulee-Recipe.TXT"
+        );
+
+        let upper = RenamePattern::new().with_case(Case::Upper);
+        assert_eq!(upper.rename("archive.tar.gz", 0, day(0)), "ARCHIVE.TAR.gz");
+        assert_eq!(upper.rename(".profile", 0, day(0)), ".PROFILE");
+    }
+
+    #[test]
+    fn invalid_names_are_conflicts() {
+        for name in ["", ".", "..", "a/b", "a\\b"] {
+            assert_eq!(Conflict::check(name), Some(Conflict::Invalid));
+        }
+        assert_eq!(Conflict::check("a.b"), None);
+    }
+
+    fn names(pattern: &RenamePattern, files: &[&str]) -> Vec<(String, String, Option<Conflict>)> {
+        let root = tempfile::tempdir().unwrap();
+        for name in files {
+            std::fs::write(root.path().join(name), "").unwrap();
+        }
+
+        let source = DirectorySource::new(root.path().to_path_buf());
+        let name = |path: &Path| path.file_name().unwrap().to_string_lossy().into_owned();
+        let mut rows: Vec<_> = preview(pattern, &source)
+            .unwrap()
+            .iter()
+            .map(|row| (name(&row.from), name(&row.to), row.conflict))
+            .collect();
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+        rows
+    }
+
+    #[test]
+    fn preview_keeps_names_taken_before_the_job() {
+        let pattern = RenamePattern::new()
+            .with_replacement(Regex::new("^([abc])").unwrap(), "${1}2".to_string());
+        let row = |from: &str, to: &str, conflict| (from.to_string(), to.to_string(), conflict);
+
+        // a2.txt is renamed too, but the job may not have moved it away yet.
+        assert_eq!(
+            names(&pattern, &["a.txt", "a2.txt", "b.txt", "keep"]),
+            [
+                row("a.txt", "a2(1).txt", Some(Conflict::Exists)),
+                row("a2.txt", "a22.txt", None),
+                row("b.txt", "b2.txt", None),
+                row("keep", "keep", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn preview_flags_files_getting_the_same_name() {
+        let pattern =
+            RenamePattern::new().with_replacement(Regex::new(r"\d").unwrap(), String::new());
+        let rows = names(&pattern, &["p1.txt", "p2.txt", "1"]);
+
+        assert_eq!(
+            rows[0],
+            ("1".to_string(), "1".to_string(), Some(Conflict::Invalid))
+        );
+        let mut renamed: Vec<_> = rows[1..].iter().map(|row| row.1.as_str()).collect();
+        renamed.sort();
+        assert_eq!(renamed, ["p(1).txt", "p.txt"]);
+        assert!(rows[1..]
+            .iter()
+            .all(|row| row.2 == Some(Conflict::Duplicate)));
+    }
+
+    #[test]
+    fn preview_lets_the_case_of_a_name_change() {
+        let pattern = RenamePattern::new().with_case(Case::Lower);
+
+        assert_eq!(
+            names(&pattern, &["Photo.JPG"]),
+            [("Photo.JPG".to_string(), "photo.JPG".to_string(), None)]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::{bail, Context};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::ffi::OsStr;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// Length of the nonce preceding every record of a mapping file.
+const NONCE_LEN: usize = 12;
+
+/// Number of bytes of the HMAC kept in a pseudonym.
+const PSEUDONYM_LEN: usize = 16;
+
+/// Keyed naming of files, see [`super::action::Naming::Pseudonym`].
+///
+/// The same file name always gets the same pseudonym under the same key, wherever the
+/// file is. Every rename is appended to an encrypted mapping file that [`reveal`] reads
+/// back with the key.
+pub struct Pseudonyms {
+    naming_key: Vec<u8>,
+    cipher: ChaCha20Poly1305,
+    mapping: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl std::fmt::Debug for Pseudonyms {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pseudonyms")
+            .field("mapping", &self.mapping)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A rename recorded in a mapping file, with paths relative to the job folder.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Mapping {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+impl Pseudonyms {
+    /// Names files with `key` and records the renames to `mapping`.
+    pub fn new(key: &[u8], mapping: PathBuf) -> Self {
+        Self {
+            naming_key: derive(key, b"rutabaga pseudonyms"),
+            cipher: cipher(key),
+            mapping,
+            lock: Default::default(),
+        }
+    }
+
+    /// Pseudonym of a file name, keeping its extension.
+    pub fn name(&self, file_name: &OsStr) -> (String, Option<String>) {
+        let mut mac = hmac(&self.naming_key);
+        mac.update(file_name.to_string_lossy().as_bytes());
+
+        let stem = mac.finalize().into_bytes()[..PSEUDONYM_LEN]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        let extension = Path::new(file_name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().into_owned());
+
+        (stem, extension)
+    }
+
+    /// Appends a rename to the mapping file as a record of its own, creating the file
+    /// and its directory if needed.
+    pub async fn record(&self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        let mapping = Mapping {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        };
+        let plaintext = serde_json::to_vec(&mapping)?;
+
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| anyhow::anyhow!("encrypting the mapping"))?;
+
+        let mut record = Vec::with_capacity(NONCE_LEN + 4 + ciphertext.len());
+        record.extend_from_slice(&nonce);
+        record.extend_from_slice(&(ciphertext.len() as u32).to_le_bytes());
+        record.extend_from_slice(&ciphertext);
+
+        let _guard = self.lock.lock().await;
+        if let Some(dir) = self
+            .mapping
+            .parent()
+            .filter(|dir| dir.as_os_str().is_empty().not())
+        {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .with_context(|| format!("creating {}", dir.display()))?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.mapping)
+            .await
+            .with_context(|| format!("opening {}", self.mapping.display()))?;
+        file.write_all(&record).await?;
+        // Written in the background otherwise, possibly after the lock is released.
+        file.flush().await?;
+
+        Ok(())
+    }
+}
+
+/// Reads back the renames recorded in a mapping file with the key they were made with.
+pub fn reveal(key: &[u8], mapping: &Path) -> anyhow::Result<Vec<Mapping>> {
+    let content =
+        std::fs::read(mapping).with_context(|| format!("reading {}", mapping.display()))?;
+    let cipher = cipher(key);
+
+    let mut renames = Vec::new();
+    let mut rest = content.as_slice();
+    while rest.is_empty().not() {
+        if rest.len() < NONCE_LEN + 4 {
+            bail!("truncated record in {}", mapping.display());
+        }
+        let (nonce, tail) = rest.split_at(NONCE_LEN);
+        let (len, tail) = tail.split_at(4);
+        let len = u32::from_le_bytes(len.try_into()?) as usize;
+        if tail.len() < len {
+            bail!("truncated record in {}", mapping.display());
+        }
+        let (ciphertext, tail) = tail.split_at(len);
+
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("wrong key or corrupted {}", mapping.display()))?;
+        renames.push(serde_json::from_slice(&plaintext)?);
+        rest = tail;
+    }
+
+    Ok(renames)
+}
+
+/// Derives a key for one purpose from the user's key, so that the same key material is
+/// never used both for naming and for encryption.
+fn derive(key: &[u8], purpose: &[u8]) -> Vec<u8> {
+    let mut mac = hmac(key);
+    mac.update(purpose);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hmac(key: &[u8]) -> Hmac<Sha256> {
+    <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
+
+fn cipher(key: &[u8]) -> ChaCha20Poly1305 {
+    let key = derive(key, b"rutabaga mapping");
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pseudonyms_depend_on_the_key_and_the_name_only() {
+        let pseudonyms = Pseudonyms::new(b"key", PathBuf::from("MAPPING"));
+        let (a, extension) = pseudonyms.name(OsStr::new("report.pdf"));
+
+        assert_eq!(a.len(), PSEUDONYM_LEN * 2);
+        assert_eq!(extension.as_deref(), Some("pdf"));
+        assert_eq!(pseudonyms.name(OsStr::new("report.pdf")).0, a);
+        assert_ne!(pseudonyms.name(OsStr::new("report.txt")).0, a);
+        assert_eq!(pseudonyms.name(OsStr::new("notes")).1, None);
+
+        let other = Pseudonyms::new(b"other key", PathBuf::from("MAPPING"));
+        assert_ne!(other.name(OsStr::new("report.pdf")).0, a);
+    }
+
+    #[tokio::test]
+    async fn mappings_are_revealed_with_the_key_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let mapping = dir.path().join("data/MAPPING");
+        let pseudonyms = Pseudonyms::new(b"key", mapping.clone());
+
+        pseudonyms
+            .record(Path::new("a/report.pdf"), Path::new("a/0f.pdf"))
+            .await
+            .unwrap();
+        pseudonyms
+            .record(Path::new("b"), Path::new("1e"))
+            .await
+            .unwrap();
+
+        let renames = reveal(b"key", &mapping).unwrap();
+        let renames: Vec<_> = renames
+            .iter()
+            .map(|rename| (rename.from.to_str().unwrap(), rename.to.to_str().unwrap()))
+            .collect();
+        assert_eq!(renames, [("a/report.pdf", "a/0f.pdf"), ("b", "1e")]);
+        assert!(reveal(b"wrong key", &mapping).is_err());
+
+        let content = std::fs::read(&mapping).unwrap();
+        std::fs::write(&mapping, &content[..content.len() - 1]).unwrap();
+        assert!(reveal(b"key", &mapping).is_err());
+    }
+}
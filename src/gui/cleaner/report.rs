@@ -0,0 +1,80 @@
+use anyhow::Context;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// What a job did to each of its files, see [`super::ClearProcess::with_report`].
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub root: PathBuf,
+    pub files: Vec<FileReport>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub directories: Vec<DirectoryReport>,
+}
+
+impl Report {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            files: Vec::new(),
+            directories: Vec::new(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    /// Path of the file when the job found it.
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub outcome: FileOutcome,
+    /// See [`super::action::FileEntry::details`].
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub details: Map<String, Value>,
+}
+
+/// A directory renamed by [`super::dirs::DirectoryRenamer`].
+#[derive(Debug, Serialize)]
+pub struct DirectoryReport {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum FileOutcome {
+    /// Every action of the pipeline ran, or one of them ended it early.
+    Processed,
+    /// An action failed and the following ones did not run.
+    Failed { action: String, error: String },
+    /// The file was left untouched.
+    Skipped { reason: SkipReason },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Another process has the file open.
+    Open,
+    /// The file was modified within the stability window.
+    Modified,
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::Open => f.write_str("open in another process"),
+            SkipReason::Modified => f.write_str("recently modified"),
+        }
+    }
+}
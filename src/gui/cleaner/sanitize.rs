@@ -0,0 +1,267 @@
+use crate::gui::cleaner::action::{move_to, ActionContext, ActionOutcome, FileAction, FileEntry};
+use async_trait::async_trait;
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::ops::Not;
+use unicode_normalization::UnicodeNormalization;
+
+/// Characters Windows or macOS do not allow in file names, control characters aside.
+const FORBIDDEN: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Names Windows reserves for devices, whatever the extension.
+const RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Renames the file to a name every common file system accepts, leaving the content
+/// alone. Files whose name is already safe are skipped.
+#[derive(Debug, Clone, Copy)]
+pub struct Sanitize {
+    max_bytes: usize,
+    replacement: char,
+}
+
+impl Default for Sanitize {
+    fn default() -> Self {
+        Self {
+            max_bytes: 255,
+            replacement: '_',
+        }
+    }
+}
+
+impl Sanitize {
+    /// Truncates names to `max_bytes` bytes of UTF-8, extension and collision suffix
+    /// included.
+    pub fn with_max_bytes(self, max_bytes: usize) -> Self {
+        Self {
+            max_bytes: max_bytes.max(16),
+            ..self
+        }
+    }
+
+    /// Replaces forbidden characters and bytes that are not UTF-8 with `replacement`.
+    pub fn with_replacement(self, replacement: char) -> Self {
+        Self {
+            replacement,
+            ..self
+        }
+    }
+
+    /// The safe version of a name, split into stem and extension.
+    ///
+    /// The name is normalized to NFC, forbidden and control characters are replaced,
+    /// leading spaces and trailing dots and spaces are removed, and reserved device
+    /// names get the replacement appended.
+    pub fn sanitize(&self, name: &[u8]) -> (String, Option<String>) {
+        let name = String::from_utf8_lossy(name)
+            .nfc()
+            .map(|c| match self.is_forbidden(c) {
+                true => self.replacement,
+                false => c,
+            })
+            .collect::<String>();
+        let name = name.trim_start_matches(' ').trim_end_matches(['.', ' ']);
+
+        let (stem, extension) = match name.rfind('.') {
+            Some(dot) if dot > 0 => (&name[..dot], Some(&name[dot + 1..])),
+            _ => (name, None),
+        };
+        let mut stem = stem.trim_end_matches(['.', ' ']).to_string();
+
+        // Windows only looks at the part before the first dot, `CON.tar` is taken too.
+        let device = stem.find('.').unwrap_or(stem.len());
+        if RESERVED
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(&stem[..device]))
+        {
+            stem.insert(device, self.replacement);
+        }
+        if stem.is_empty() {
+            stem.push(self.replacement);
+        }
+
+        // The extension is kept whole unless it alone goes over the limit.
+        let extension = extension
+            .map(|extension| truncate(extension, self.max_bytes / 2).to_string())
+            .filter(|extension| extension.is_empty().not());
+        let room = self.max_bytes
+            - extension
+                .as_ref()
+                .map_or(0, |extension| extension.len() + 1);
+
+        (truncate(&stem, room).to_string(), extension)
+    }
+
+    fn is_forbidden(&self, c: char) -> bool {
+        c.is_control() || c == char::REPLACEMENT_CHARACTER || FORBIDDEN.contains(&c)
+    }
+}
+
+#[async_trait]
+impl FileAction for Sanitize {
+    fn label(&self) -> &str {
+        "Sanitized"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        let name = entry.path.file_name().unwrap_or_default();
+        let (mut stem, extension) = self.sanitize(&raw_name(name));
+
+        let safe = match &extension {
+            None => stem.clone(),
+            Some(extension) => format!("{stem}.{extension}"),
+        };
+        if name.to_str() == Some(safe.as_str()) {
+            return ActionOutcome::Skipped;
+        }
+
+        let dir = entry.path.parent().unwrap_or(&ctx.root);
+        let new_path = loop {
+            let new_path = ctx.outputs.claim(dir, &stem, extension.as_deref());
+            let len = new_path.file_name().map_or(0, |name| name.len());
+
+            // A collision suffix may push the name over the limit again.
+            match len.checked_sub(self.max_bytes) {
+                None | Some(0) => break new_path,
+                Some(excess) => {
+                    let shorter = truncate(&stem, stem.len().saturating_sub(excess));
+                    if shorter.is_empty() {
+                        break new_path;
+                    }
+
+                    stem = shorter.to_string();
+                    ctx.outputs.remove(&new_path);
+                }
+            }
+        };
+
+        move_to(ctx, entry, new_path).await.map(|_| ()).into()
+    }
+}
+
+#[cfg(unix)]
+fn raw_name(name: &OsStr) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Cow::Borrowed(name.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn raw_name(name: &OsStr) -> Cow<'_, [u8]> {
+    Cow::Owned(name.to_string_lossy().into_owned().into_bytes())
+}
+
+/// The longest prefix of `text` of at most `max_bytes` bytes that ends on a character.
+fn truncate(text: &str, max_bytes: usize) -> &str {
+    match text.len() <= max_bytes {
+        true => text,
+        false => {
+            let end = (0..=max_bytes)
+                .rev()
+                .find(|i| text.is_char_boundary(*i))
+                .unwrap_or(0);
+            &text[..end]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui::cleaner::action::Counters;
+    use crate::gui::cleaner::io::TokioBackend;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn sanitized(sanitize: &Sanitize, name: &str) -> String {
+        match sanitize.sanitize(name.as_bytes()) {
+            (stem, None) => stem,
+            (stem, Some(extension)) => format!("{stem}.{extension}"),
+        }
+    }
+
+    fn context(root: &Path) -> ActionContext {
+        ActionContext {
+            root: root.to_path_buf(),
+            outputs: Default::default(),
+            io: Arc::new(TokioBackend),
+        }
+    }
+
+    #[test]
+    fn names_lose_forbidden_characters_and_trailing_dots() {
+        let sanitize = Sanitize::default();
+
+        assert_eq!(sanitized(&sanitize, "report.pdf"), "report.pdf");
+        assert_eq!(
+            sanitized(&sanitize, "a<b>:c\"d|e?f*.txt"),
+            "a_b__c_d_e_f_.txt"
+        );
+        assert_eq!(sanitized(&sanitize, "tab\there\n.txt"), "tab_here_.txt");
+        assert_eq!(sanitized(&sanitize, "  notes .. txt. . "), "notes. txt");
+        assert_eq!(sanitized(&sanitize, "draft .txt"), "draft.txt");
+        assert_eq!(sanitized(&sanitize, "..."), "_");
+        assert_eq!(sanitized(&sanitize.with_replacement('-'), "a?b"), "a-b");
+        // Decomposed `é` as stored by older macOS file systems.
+        assert_eq!(sanitized(&sanitize, "cafe\u{301}.txt"), "caf\u{e9}.txt");
+        assert_eq!(
+            sanitize.sanitize(b"bad\xffbyte"),
+            ("bad_byte".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn reserved_device_names_get_the_replacement() {
+        let sanitize = Sanitize::default();
+
+        assert_eq!(sanitized(&sanitize, "CON"), "CON_");
+        assert_eq!(sanitized(&sanitize, "nul.txt"), "nul_.txt");
+        assert_eq!(sanitized(&sanitize, "com1.tar.gz"), "com1_.tar.gz");
+        assert_eq!(sanitized(&sanitize, "CONSOLE.txt"), "CONSOLE.txt");
+        assert_eq!(sanitized(&sanitize, "LPT10"), "LPT10");
+    }
+
+    #[test]
+    fn long_names_are_cut_on_characters_keeping_the_extension() {
+        let sanitize = Sanitize::default().with_max_bytes(20);
+
+        assert_eq!(
+            sanitized(&sanitize, "abcdefghijklmnopqrstuvwxyz.txt"),
+            "abcdefghijklmnop.txt"
+        );
+        assert_eq!(sanitized(&sanitize, "ééééééééééé.txt"), "éééééééé.txt");
+        assert_eq!(
+            sanitized(&sanitize, "a.abcdefghijklmnopqrstuvwxyz"),
+            "a.abcdefghij"
+        );
+        assert_eq!(Sanitize::default().with_max_bytes(1).max_bytes, 16);
+        assert_eq!(truncate("aé", 2), "a");
+    }
+
+    #[tokio::test]
+    async fn unsafe_names_are_renamed_within_the_limit() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context(root.path());
+        let sanitize = Sanitize::default().with_max_bytes(16);
+        for name in ["safe.txt", "abcdefghijkl.txt", "abcdefghijkl?.txt"] {
+            std::fs::write(root.path().join(name), name).unwrap();
+        }
+
+        let mut outcomes = Vec::new();
+        for name in ["safe.txt", "abcdefghijkl?.txt"] {
+            let path = root.path().join(name);
+            let mut entry = FileEntry::new(0, path, 1, Arc::new(Counters::new(1)));
+            outcomes.push(sanitize.apply(&ctx, &mut entry).await);
+        }
+
+        assert!(matches!(outcomes[0], ActionOutcome::Skipped));
+        assert!(matches!(outcomes[1], ActionOutcome::Applied));
+        // Cut to `abcdefghijkl.txt`, taken, and `abcdefghijkl(1).txt` is too long.
+        assert_eq!(
+            std::fs::read_to_string(root.path().join("abcdefghi.txt")).unwrap(),
+            "abcdefghijkl?.txt"
+        );
+    }
+}
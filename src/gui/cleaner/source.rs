@@ -0,0 +1,661 @@
+use crate::gui::cleaner::action::Outputs;
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Display, Formatter};
+use std::fs::{File, ReadDir};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Number of discovered files buffered ahead of processing.
+const BUFFER: usize = 4096;
+
+pub type Files<'a> = Box<dyn Iterator<Item = PathBuf> + Send + 'a>;
+
+/// Where the files of a job come from.
+///
+/// Sources may return paths that are not regular files; the engine skips them.
+pub trait FileSource: Debug + Display + Send + Sync {
+    fn files(&self) -> anyhow::Result<Files<'_>>;
+}
+
+/// The entries of a directory, walking into subdirectories when recursive.
+#[derive(Debug)]
+pub struct DirectorySource {
+    path: PathBuf,
+    recursive: bool,
+}
+
+impl DirectorySource {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            recursive: false,
+        }
+    }
+
+    /// Also lists the files of every subdirectory. Symbolic links to directories are
+    /// not followed.
+    pub fn recursive(path: PathBuf) -> Self {
+        Self {
+            path,
+            recursive: true,
+        }
+    }
+}
+
+impl Display for DirectorySource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.recursive {
+            true => write!(f, "Files in {} and below", self.path.display()),
+            false => write!(f, "Files in {}", self.path.display()),
+        }
+    }
+}
+
+impl FileSource for DirectorySource {
+    fn files(&self) -> anyhow::Result<Files<'_>> {
+        let entries = std::fs::read_dir(&self.path)?;
+
+        if self.recursive.not() {
+            return Ok(Box::new(
+                entries.filter_map(|entry| entry.ok().map(|entry| entry.path())),
+            ));
+        }
+
+        Ok(Box::new(Walk {
+            stack: vec![entries],
+        }))
+    }
+}
+
+/// Depth-first walk yielding every entry that is not a directory.
+struct Walk {
+    stack: Vec<ReadDir>,
+}
+
+impl Iterator for Walk {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<PathBuf> {
+        loop {
+            let entry = match self.stack.last_mut()?.next() {
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+                Some(Err(_)) => continue,
+                Some(Ok(entry)) => entry,
+            };
+
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => {
+                    if let Ok(entries) = std::fs::read_dir(entry.path()) {
+                        self.stack.push(entries);
+                    }
+                }
+                _ => return Some(entry.path()),
+            }
+        }
+    }
+}
+
+/// Paths read from a list file, or from stdin when the list is `-`.
+///
+/// Relative paths are resolved against `base`.
+#[derive(Debug)]
+pub struct ListSource {
+    list: PathBuf,
+    delimiter: u8,
+    base: PathBuf,
+}
+
+impl ListSource {
+    pub fn new(list: PathBuf, delimiter: u8, base: PathBuf) -> Self {
+        Self {
+            list,
+            delimiter,
+            base,
+        }
+    }
+}
+
+impl Display for ListSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.list.to_str() {
+            Some("-") => write!(f, "Files listed on stdin"),
+            _ => write!(f, "Files listed in {}", self.list.display()),
+        }
+    }
+}
+
+impl FileSource for ListSource {
+    fn files(&self) -> anyhow::Result<Files<'_>> {
+        let reader: Box<dyn Read + Send> = match self.list.to_str() {
+            Some("-") => Box::new(std::io::stdin()),
+            _ => Box::new(
+                File::open(&self.list)
+                    .with_context(|| format!("opening {}", self.list.display()))?,
+            ),
+        };
+
+        let paths = BufReader::new(reader)
+            .split(self.delimiter)
+            .map_while(Result::ok)
+            .filter_map(move |line| {
+                let line = match self.delimiter {
+                    b'\n' => line.strip_suffix(b"\r").unwrap_or(&line),
+                    _ => &line,
+                };
+                if line.is_empty() {
+                    return None;
+                }
+
+                Some(self.base.join(path_from_bytes(line)))
+            });
+
+        Ok(Box::new(paths))
+    }
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Paths matching a glob expression. Relative patterns are resolved against `base`.
+#[derive(Debug)]
+pub struct GlobSource {
+    pattern: String,
+    base: PathBuf,
+}
+
+impl GlobSource {
+    pub fn new(pattern: String, base: PathBuf) -> Self {
+        Self { pattern, base }
+    }
+}
+
+impl Display for GlobSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Files matching {}", self.pattern)
+    }
+}
+
+impl FileSource for GlobSource {
+    fn files(&self) -> anyhow::Result<Files<'_>> {
+        let pattern = match Path::new(&self.pattern).is_absolute() {
+            true => self.pattern.clone(),
+            false => {
+                // Brackets, `*` and `?` in the folder names are matched as they are.
+                let base = self
+                    .base
+                    .to_str()
+                    .context("glob base folder is not valid UTF-8")?;
+                let base = PathBuf::from(glob::Pattern::escape(base));
+                base.join(&self.pattern).to_string_lossy().into_owned()
+            }
+        };
+        let paths = glob::glob(&pattern)?;
+
+        Ok(Box::new(paths.filter_map(Result::ok)))
+    }
+}
+
+/// Untracked, not ignored files of a git repository.
+#[derive(Debug)]
+pub struct GitUntrackedSource {
+    repository: PathBuf,
+}
+
+impl GitUntrackedSource {
+    pub fn new(repository: PathBuf) -> Self {
+        Self { repository }
+    }
+}
+
+impl Display for GitUntrackedSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Untracked files of {}", self.repository.display())
+    }
+}
+
+impl FileSource for GitUntrackedSource {
+    fn files(&self) -> anyhow::Result<Files<'_>> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.repository)
+            .args(["ls-files", "--others", "--exclude-standard", "-z"])
+            .output()
+            .context("running git")?;
+
+        if output.status.success().not() {
+            bail!(
+                "git ls-files failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let paths = output
+            .stdout
+            .split(|byte| *byte == 0)
+            .filter(|path| path.is_empty().not())
+            .map(|path| self.repository.join(path_from_bytes(path)))
+            .collect::<Vec<_>>();
+
+        Ok(Box::new(paths.into_iter()))
+    }
+}
+
+/// Files recorded by a previous job, see [`Enumeration::start`].
+#[derive(Debug)]
+pub struct ScanSource {
+    scan: PathBuf,
+}
+
+impl ScanSource {
+    pub fn new(scan: PathBuf) -> Self {
+        Self { scan }
+    }
+}
+
+impl Display for ScanSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Files of the scan {}", self.scan.display())
+    }
+}
+
+impl FileSource for ScanSource {
+    fn files(&self) -> anyhow::Result<Files<'_>> {
+        let scan = Scan::load(&self.scan)?;
+
+        Ok(Box::new(scan.files.into_iter()))
+    }
+}
+
+/// Files picked one by one, from the findings of a discovery scan for instance.
+#[derive(Debug)]
+pub struct SelectionSource {
+    files: Vec<PathBuf>,
+}
+
+impl SelectionSource {
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        Self { files }
+    }
+}
+
+impl Display for SelectionSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.files.len() {
+            1 => write!(f, "1 selected file"),
+            len => write!(f, "{len} selected files"),
+        }
+    }
+}
+
+impl FileSource for SelectionSource {
+    fn files(&self) -> anyhow::Result<Files<'_>> {
+        Ok(Box::new(self.files.iter().cloned()))
+    }
+}
+
+/// The files found by a job, as stored on disk.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Scan {
+    pub root: PathBuf,
+    pub files: Vec<PathBuf>,
+}
+
+impl Scan {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+}
+
+/// Writes a [`Scan`] one file at a time, without holding the file list in memory.
+struct ScanWriter {
+    writer: BufWriter<File>,
+    empty: bool,
+}
+
+impl ScanWriter {
+    fn create(path: &Path, root: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+
+        writer.write_all(b"{\"root\":")?;
+        serde_json::to_writer(&mut writer, root)?;
+        writer.write_all(b",\"files\":[")?;
+
+        Ok(Self {
+            writer,
+            empty: true,
+        })
+    }
+
+    fn push(&mut self, path: &Path) -> anyhow::Result<()> {
+        if self.empty.not() {
+            self.writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut self.writer, path)?;
+        self.empty = false;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> anyhow::Result<()> {
+        self.writer.write_all(b"]}")?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Files of a source, listed on a blocking worker and streamed to the engine as
+/// they are found.
+#[derive(Debug)]
+pub struct Enumeration {
+    receiver: mpsc::Receiver<anyhow::Result<PathBuf>>,
+    discovered: Arc<AtomicUsize>,
+    bytes: Arc<AtomicU64>,
+    finished: Arc<AtomicBool>,
+}
+
+impl Enumeration {
+    /// Starts listing the regular files of `source`, recording them to `scan` if given.
+    /// Paths found in `outputs` were created by the job itself and are skipped, and so
+    /// are the `side_files` it writes to, such as its report and `scan`.
+    ///
+    /// The worker stops as soon as the enumeration is dropped.
+    pub fn start(
+        source: Arc<dyn FileSource>,
+        root: PathBuf,
+        scan: Option<PathBuf>,
+        outputs: Arc<Outputs>,
+        side_files: Vec<PathBuf>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(BUFFER);
+        let discovered = Arc::new(AtomicUsize::new(0));
+        let bytes = Arc::new(AtomicU64::new(0));
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let worker = Worker {
+            discovered: discovered.clone(),
+            bytes: bytes.clone(),
+            finished: finished.clone(),
+            outputs,
+            side_files: SideFiles::new(side_files.iter().chain(&scan)),
+            sender,
+        };
+        tokio::task::spawn_blocking(move || worker.run(source.as_ref(), &root, scan));
+
+        Self {
+            receiver,
+            discovered,
+            bytes,
+            finished,
+        }
+    }
+
+    pub async fn next(&mut self) -> Option<anyhow::Result<PathBuf>> {
+        self.receiver.recv().await
+    }
+
+    pub fn discovered(&self) -> usize {
+        self.discovered.load(Ordering::SeqCst)
+    }
+
+    /// Number of files of the source, once all of them have been found.
+    pub fn total(&self) -> Option<usize> {
+        match self.finished.load(Ordering::SeqCst) {
+            true => Some(self.discovered()),
+            false => None,
+        }
+    }
+
+    /// Size of the files found so far, in bytes.
+    pub fn discovered_bytes(&self) -> u64 {
+        self.bytes.load(Ordering::SeqCst)
+    }
+
+    /// Size of the files of the source, once all of them have been found.
+    pub fn total_bytes(&self) -> Option<u64> {
+        match self.finished.load(Ordering::SeqCst) {
+            true => Some(self.discovered_bytes()),
+            false => None,
+        }
+    }
+}
+
+struct Worker {
+    discovered: Arc<AtomicUsize>,
+    bytes: Arc<AtomicU64>,
+    finished: Arc<AtomicBool>,
+    outputs: Arc<Outputs>,
+    side_files: SideFiles,
+    sender: mpsc::Sender<anyhow::Result<PathBuf>>,
+}
+
+impl Worker {
+    fn run(self, source: &dyn FileSource, root: &Path, scan: Option<PathBuf>) {
+        if let Err(err) = self.enumerate(source, root, scan) {
+            let _ = self.sender.blocking_send(Err(err));
+        }
+    }
+
+    fn enumerate(
+        &self,
+        source: &dyn FileSource,
+        root: &Path,
+        scan: Option<PathBuf>,
+    ) -> anyhow::Result<()> {
+        let mut scan = match scan {
+            None => None,
+            Some(scan) => Some(ScanWriter::create(&scan, root)?),
+        };
+
+        let files = source
+            .files()?
+            .filter(|path| self.outputs.remove(path).not())
+            .filter(|path| self.side_files.contains(path).not())
+            .filter_map(|path| match std::fs::metadata(&path) {
+                Ok(metadata) if metadata.is_file() => Some((path, metadata.len())),
+                _ => None,
+            });
+
+        for (path, len) in files {
+            if let Some(scan) = &mut scan {
+                scan.push(&path)?;
+            }
+
+            self.discovered.fetch_add(1, Ordering::SeqCst);
+            self.bytes.fetch_add(len, Ordering::SeqCst);
+            if self.sender.blocking_send(Ok(path)).is_err() {
+                // The job is gone, nobody is waiting for more files.
+                return Ok(());
+            }
+        }
+
+        if let Some(scan) = scan {
+            scan.finish()?;
+        }
+
+        self.finished.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Files a job writes to besides the files it processes, with their directory resolved.
+struct SideFiles(Vec<PathBuf>);
+
+impl SideFiles {
+    fn new<'a>(paths: impl IntoIterator<Item = &'a PathBuf>) -> Self {
+        Self(paths.into_iter().filter_map(|path| resolve(path)).collect())
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        // Comparing names first spares resolving every path of the job.
+        self.0.iter().any(|side| {
+            side.file_name() == path.file_name() && resolve(path).as_ref() == Some(side)
+        })
+    }
+}
+
+/// `path` with its directory made absolute and free of symbolic links, for files that
+/// may not exist yet.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?;
+    let dir = match path.parent() {
+        Some(dir) if dir.as_os_str().is_empty().not() => dir,
+        _ => Path::new("."),
+    };
+
+    Some(dir.canonicalize().ok()?.join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("sub/deeper")).unwrap();
+        for file in ["a", "sub/b", "sub/deeper/c"] {
+            std::fs::write(root.path().join(file), file).unwrap();
+        }
+        root
+    }
+
+    fn sorted(files: Files<'_>) -> Vec<PathBuf> {
+        let mut files: Vec<_> = files.collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn directories_are_walked_only_when_recursive() {
+        let root = tree();
+        let path = root.path().to_path_buf();
+
+        let flat = DirectorySource::new(path.clone());
+        assert_eq!(
+            sorted(flat.files().unwrap()),
+            [path.join("a"), path.join("sub")]
+        );
+
+        let recursive = DirectorySource::recursive(path.clone());
+        assert_eq!(
+            sorted(recursive.files().unwrap()),
+            [
+                path.join("a"),
+                path.join("sub/b"),
+                path.join("sub/deeper/c")
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_resolve_relative_paths_and_skip_blank_lines() {
+        let root = tempfile::tempdir().unwrap();
+        let base = PathBuf::from("/base");
+
+        let lines = root.path().join("lines");
+        std::fs::write(&lines, "a\r\n\nsub/b\n/abs\n").unwrap();
+        let source = ListSource::new(lines, b'\n', base.clone());
+        assert_eq!(
+            source.files().unwrap().collect::<Vec<_>>(),
+            [base.join("a"), base.join("sub/b"), PathBuf::from("/abs")]
+        );
+
+        let nul = root.path().join("nul");
+        std::fs::write(&nul, "with\nnewline\0b\0").unwrap();
+        let source = ListSource::new(nul, b'\0', base.clone());
+        assert_eq!(
+            source.files().unwrap().collect::<Vec<_>>(),
+            [base.join("with\nnewline"), base.join("b")]
+        );
+    }
+
+    #[test]
+    fn globs_are_relative_to_the_base() {
+        let root = tree();
+        let source = GlobSource::new("**/[bc]".to_string(), root.path().to_path_buf());
+
+        assert_eq!(
+            sorted(source.files().unwrap()),
+            [root.path().join("sub/b"), root.path().join("sub/deeper/c")]
+        );
+    }
+
+    #[test]
+    fn glob_bases_are_matched_literally() {
+        let root = tempfile::tempdir().unwrap();
+        let base = root.path().join("[2022] exports*");
+        std::fs::create_dir(&base).unwrap();
+        std::fs::create_dir(root.path().join("2 exports")).unwrap();
+        for file in ["a.csv", "b.txt"] {
+            std::fs::write(base.join(file), file).unwrap();
+            std::fs::write(root.path().join("2 exports").join(file), file).unwrap();
+        }
+
+        let source = GlobSource::new("*.csv".to_string(), base.clone());
+        assert_eq!(sorted(source.files().unwrap()), [base.join("a.csv")]);
+
+        let absolute = root.path().join("2 exports/*.txt");
+        let source = GlobSource::new(absolute.to_string_lossy().into_owned(), base);
+        assert_eq!(
+            sorted(source.files().unwrap()),
+            [root.path().join("2 exports/b.txt")]
+        );
+    }
+
+    #[tokio::test]
+    async fn enumeration_skips_outputs_and_saves_the_scan() {
+        let root = tree();
+        let path = root.path().to_path_buf();
+        let scan = root.path().join("scan.json");
+        std::fs::write(root.path().join("sub/report.json"), "{}").unwrap();
+        let outputs = Arc::new(Outputs::default());
+        let claimed = outputs.claim(&path.join("sub"), "b", None);
+        assert_eq!(claimed, path.join("sub/b(1)"));
+        std::fs::rename(path.join("sub/b"), &claimed).unwrap();
+
+        let mut files = Enumeration::start(
+            Arc::new(DirectorySource::recursive(path.clone())),
+            path.clone(),
+            Some(scan.clone()),
+            outputs,
+            vec![path.join("sub/../sub/report.json")],
+        );
+        let mut found = Vec::new();
+        while let Some(file) = files.next().await {
+            found.push(file.unwrap());
+        }
+        found.sort();
+
+        assert_eq!(found, [path.join("a"), path.join("sub/deeper/c")]);
+        assert_eq!(files.total(), Some(2));
+        assert_eq!(files.total_bytes(), Some(1 + 12));
+
+        let saved = Scan::load(&scan).unwrap();
+        assert_eq!(saved.root, path);
+        let mut saved = saved.files;
+        saved.sort();
+        assert_eq!(saved, found);
+        assert_eq!(sorted(ScanSource::new(scan).files().unwrap()), found);
+    }
+}
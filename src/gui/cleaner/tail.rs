@@ -0,0 +1,296 @@
+use crate::gui::cleaner::action::{
+    ActionContext, ActionOutcome, FileAction, FileEntry, Replacement,
+};
+use async_trait::async_trait;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::Write;
+use std::path::Path;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Cuts the file down to its end, for logs that should be kept short rather than
+/// emptied.
+///
+/// The end is copied to a temporary file next to the original, which then replaces it
+/// with a rename, so the file is never seen half-written. Lines appended by another
+/// program during the copy are lost.
+#[derive(Debug, Clone, Copy)]
+pub struct KeepTail {
+    keep: Tail,
+    archive: bool,
+}
+
+/// How much of the end of a file [`KeepTail`] keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tail {
+    Lines(u64),
+    Bytes(u64),
+}
+
+impl KeepTail {
+    pub fn new(keep: Tail) -> Self {
+        Self {
+            keep,
+            archive: false,
+        }
+    }
+
+    /// Compresses the part that is cut to `{name}.head.gz` next to the file.
+    pub fn with_archive(self, archive: bool) -> Self {
+        Self { archive, ..self }
+    }
+
+    /// Cuts the file and returns whether there was anything to cut.
+    async fn cut(&self, ctx: &ActionContext, entry: &FileEntry) -> anyhow::Result<bool> {
+        let path = &entry.path;
+        let len = tokio::fs::metadata(path).await?.len();
+
+        let cut = match self.keep {
+            Tail::Bytes(bytes) => len.saturating_sub(bytes),
+            Tail::Lines(lines) => start_of_last_lines(ctx, path, len, lines).await?,
+        };
+        if cut == 0 {
+            return Ok(false);
+        }
+
+        if self.archive {
+            let dir = path.parent().unwrap_or(&ctx.root);
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let archive = ctx.outputs.claim(dir, &format!("{name}.head"), Some("gz"));
+            if let Err(err) = archive_head(ctx, cut, &archive, entry).await {
+                ctx.outputs.remove(&archive);
+                let _ = ctx.io.remove(&archive).await;
+                return Err(err);
+            }
+        }
+
+        let mut replacement = Replacement::create(ctx, path).await?;
+        match copy_tail(ctx, cut, &mut replacement, entry).await {
+            Ok(_) => replacement.commit(ctx).await?,
+            Err(err) => {
+                replacement.discard(ctx).await;
+                return Err(err);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl FileAction for KeepTail {
+    fn label(&self) -> &str {
+        "Cut"
+    }
+
+    async fn apply(&self, ctx: &ActionContext, entry: &mut FileEntry) -> ActionOutcome {
+        match self.cut(ctx, entry).await {
+            Ok(true) => ActionOutcome::Applied,
+            Ok(false) => ActionOutcome::Skipped,
+            Err(err) => ActionOutcome::Failed(err),
+        }
+    }
+}
+
+/// Offset of the first of the last `lines` lines of the file `path` of `len` bytes. A
+/// newline ending the file does not start another line.
+async fn start_of_last_lines(
+    ctx: &ActionContext,
+    path: &Path,
+    len: u64,
+    lines: u64,
+) -> anyhow::Result<u64> {
+    if lines == 0 {
+        return Ok(len);
+    }
+
+    let mut end = len;
+    let mut newlines = 0;
+
+    while end > 0 {
+        let start = end.saturating_sub(CHUNK_SIZE as u64);
+        let chunk = ctx.io.read_at(path, start, (end - start) as usize).await?;
+        if chunk.len() as u64 != end - start {
+            anyhow::bail!("the file was cut short while being read");
+        }
+
+        for (i, byte) in chunk.iter().enumerate().rev() {
+            let offset = start + i as u64;
+            if *byte == b'\n' && offset != len - 1 {
+                newlines += 1;
+                if newlines == lines {
+                    return Ok(offset + 1);
+                }
+            }
+        }
+
+        end = start;
+    }
+
+    Ok(0)
+}
+
+/// Compresses the first `cut` bytes of the file to `archive`.
+async fn archive_head(
+    ctx: &ActionContext,
+    cut: u64,
+    archive: &Path,
+    entry: &FileEntry,
+) -> anyhow::Result<()> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let mut position = 0;
+    let mut written = 0;
+    ctx.io.write(archive, Vec::new()).await?;
+
+    while position < cut {
+        let len = (cut - position).min(CHUNK_SIZE as u64) as usize;
+        let chunk = ctx.io.read_at(&entry.path, position, len).await?;
+        if chunk.len() != len {
+            anyhow::bail!("the file was cut short while being read");
+        }
+        encoder.write_all(&chunk)?;
+
+        // The encoder only buffers what it has compressed so far.
+        let compressed = std::mem::take(encoder.get_mut());
+        let compressed_len = compressed.len() as u64;
+        ctx.io.write_at(archive, written, compressed).await?;
+        written += compressed_len;
+
+        position += len as u64;
+        entry.advance(position);
+    }
+
+    ctx.io.write_at(archive, written, encoder.finish()?).await?;
+    ctx.io.fsync(archive).await?;
+
+    Ok(())
+}
+
+/// Copies the file from `cut` on to `replacement`.
+async fn copy_tail(
+    ctx: &ActionContext,
+    cut: u64,
+    replacement: &mut Replacement,
+    entry: &FileEntry,
+) -> anyhow::Result<()> {
+    let mut position = cut;
+
+    loop {
+        let chunk = ctx.io.read_at(&entry.path, position, CHUNK_SIZE).await?;
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        position += chunk.len() as u64;
+        replacement.write(ctx, chunk).await?;
+        entry.advance(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gui::cleaner::action::Counters;
+    use crate::gui::cleaner::io::TokioBackend;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use std::sync::Arc;
+
+    fn context(root: &Path) -> ActionContext {
+        ActionContext {
+            root: root.to_path_buf(),
+            outputs: Default::default(),
+            io: Arc::new(TokioBackend),
+        }
+    }
+
+    async fn keep_tail(keep: KeepTail, root: &Path, content: &[u8]) -> (ActionOutcome, Vec<u8>) {
+        let path = root.join("app.log");
+        std::fs::write(&path, content).unwrap();
+        let mut entry = FileEntry::new(
+            0,
+            path.clone(),
+            content.len() as u64,
+            Arc::new(Counters::new(1)),
+        );
+
+        let outcome = keep.apply(&context(root), &mut entry).await;
+        (outcome, std::fs::read(&path).unwrap())
+    }
+
+    #[tokio::test]
+    async fn the_last_lines_or_bytes_are_kept() {
+        let root = tempfile::tempdir().unwrap();
+        let lines = KeepTail::new(Tail::Lines(2));
+
+        let (outcome, content) = keep_tail(lines, root.path(), b"a\nb\nc\nd\n").await;
+        assert!(matches!(outcome, ActionOutcome::Applied));
+        assert_eq!(content, b"c\nd\n");
+        let (_, content) = keep_tail(lines, root.path(), b"a\nb\nc").await;
+        assert_eq!(content, b"b\nc");
+        let (outcome, _) = keep_tail(lines, root.path(), b"a\nb\n").await;
+        assert!(matches!(outcome, ActionOutcome::Skipped));
+
+        let (_, content) = keep_tail(KeepTail::new(Tail::Bytes(3)), root.path(), b"abcdef").await;
+        assert_eq!(content, b"def");
+        let (_, content) = keep_tail(KeepTail::new(Tail::Bytes(0)), root.path(), b"abcdef").await;
+        assert_eq!(content, b"");
+
+        // Only the log is left, no temporary file.
+        let names: Vec<_> = std::fs::read_dir(root.path()).unwrap().collect();
+        assert_eq!(names.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn long_files_are_cut_across_chunks() {
+        let root = tempfile::tempdir().unwrap();
+        let line = format!("{}\n", "x".repeat(99));
+        let content = line.repeat(2 * CHUNK_SIZE / 100 + 10);
+
+        let (_, kept) = keep_tail(
+            KeepTail::new(Tail::Lines(700)),
+            root.path(),
+            content.as_bytes(),
+        )
+        .await;
+        assert_eq!(kept, line.repeat(700).as_bytes());
+    }
+
+    #[tokio::test]
+    async fn the_cut_head_is_archived() {
+        let root = tempfile::tempdir().unwrap();
+        let keep = KeepTail::new(Tail::Lines(1)).with_archive(true);
+
+        let (outcome, content) = keep_tail(keep, root.path(), b"old\nnew\n").await;
+        assert!(matches!(outcome, ActionOutcome::Applied));
+        assert_eq!(content, b"new\n");
+
+        let archive = std::fs::File::open(root.path().join("app.log.head.gz")).unwrap();
+        let mut head = String::new();
+        GzDecoder::new(archive).read_to_string(&mut head).unwrap();
+        assert_eq!(head, "old\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn permissions_are_kept() {
+        use std::os::unix::fs::PermissionsExt;
+
+        for mode in [0o600, 0o444] {
+            let root = tempfile::tempdir().unwrap();
+            let path = root.path().join("app.log");
+            std::fs::write(&path, "a\nb\n").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+            let mut entry = FileEntry::new(0, path.clone(), 4, Arc::new(Counters::new(1)));
+
+            let outcome = KeepTail::new(Tail::Lines(1))
+                .apply(&context(root.path()), &mut entry)
+                .await;
+            assert!(matches!(outcome, ActionOutcome::Applied), "{mode:o}");
+            assert_eq!(std::fs::read(&path).unwrap(), b"b\n");
+            let permissions = std::fs::metadata(&path).unwrap().permissions();
+            assert_eq!(permissions.mode() & 0o777, mode);
+        }
+    }
+}
@@ -1,22 +1,56 @@
-use crate::gui::cleaner::ClearProcess;
-use crate::gui::style::{PrimaryButtonStyle, SecondaryButtonStyle};
+use crate::cli::Args;
+use crate::gui::cleaner::action::Pipeline;
+use crate::gui::cleaner::discover::{self, Discover, Finding, Findings};
+use crate::gui::cleaner::limit::Limits;
+use crate::gui::cleaner::pattern::{self, PreviewRow};
+use crate::gui::cleaner::source::{DirectorySource, FileSource, SelectionSource};
+use crate::gui::cleaner::{ClearProcess, Operation, Snapshot};
+use crate::gui::style::{HeaderButtonStyle, PrimaryButtonStyle, SecondaryButtonStyle};
 use iced::alignment::{Horizontal, Vertical};
+use iced::canvas::{self, Canvas, Cursor, Frame, Geometry};
 use iced::{
-    button, container, text_input, window::Settings as Window, Alignment, Application, Background,
-    Button, Color, Column, Command, Container, Element, Length, Padding, Renderer, Row, Settings,
-    Text, TextInput,
+    button, container, pick_list, scrollable, text_input, window::Settings as Window, Alignment,
+    Application, Background, Button, Checkbox, Color, Column, Command, Container, Element, Length,
+    Padding, PickList, Point, Rectangle, Renderer, Row, Scrollable, Settings, Size, Space, Text,
+    TextInput,
 };
 use iced_native::Subscription;
-use std::path::PathBuf;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
-mod cleaner;
+pub(crate) mod cleaner;
 mod style;
 
+/// Most files listed in the rename preview, which only counts the others.
+const PREVIEW_ROWS: usize = 500;
+
+/// Most files listed in the findings of the discovery scan.
+const FINDING_ROWS: usize = 500;
+
 pub struct RutabagaApplication {
     path_folder: PathBuf,
     path_folder_button_state: ButtonState,
     path_folder_input_state: text_input::State,
 
+    operation: Operation,
+    operation_state: pick_list::State<Operation>,
+    scrub: bool,
+    args: Args,
+
+    limits: Arc<Limits>,
+    rate_limits: Vec<RateLimit>,
+    rate_limit_state: pick_list::State<RateLimit>,
+    ops_limits: Vec<OpsLimit>,
+    ops_limit_state: pick_list::State<OpsLimit>,
+
+    preview: Option<Result<Vec<PreviewRow>, String>>,
+    preview_button_state: ButtonState,
+    preview_scroll_state: scrollable::State,
+
+    discovery: Discovery,
+
     start_button_state: ButtonState,
     stop_button_state: ButtonState,
 
@@ -37,12 +71,92 @@ pub enum Message {
     PathInputChanged(String),
     SelectFolder,
     SelectedFolder(Option<PathBuf>),
+    OperationSelected(Operation),
+    ScrubToggled(bool),
+    RateLimitSelected(RateLimit),
+    OpsLimitSelected(OpsLimit),
+    IdleToggled(bool),
+    PreviewRename,
+    RenamePreviewed(Result<Vec<PreviewRow>, String>),
+    Discover,
+    SortFindings(FindingColumn),
+    FindingToggled(usize, bool),
+    AllFindingsToggled(bool),
+    ExportFindings(ExportFormat),
+    FindingsExported(Result<Option<PathBuf>, String>),
+    CleanSelected,
     Clear(()),
     ProcessStart,
     ProcessCancel,
     Process(cleaner::Progress),
 }
 
+/// Bytes read or written per second, `None` when unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RateLimit(Option<u64>);
+
+impl RateLimit {
+    const PRESETS: [RateLimit; 6] = [
+        RateLimit(None),
+        RateLimit(Some(1 << 20)),
+        RateLimit(Some(10 << 20)),
+        RateLimit(Some(50 << 20)),
+        RateLimit(Some(100 << 20)),
+        RateLimit(Some(500 << 20)),
+    ];
+
+    fn options(current: RateLimit) -> Vec<RateLimit> {
+        limit_options(&Self::PRESETS, current)
+    }
+}
+
+impl std::fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            None => f.write_str("Unlimited"),
+            Some(rate) => write!(f, "{}/s", format_bytes(rate as f64)),
+        }
+    }
+}
+
+/// Backend operations per second, `None` when unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OpsLimit(Option<u64>);
+
+impl OpsLimit {
+    const PRESETS: [OpsLimit; 5] = [
+        OpsLimit(None),
+        OpsLimit(Some(10)),
+        OpsLimit(Some(100)),
+        OpsLimit(Some(1000)),
+        OpsLimit(Some(10000)),
+    ];
+
+    fn options(current: OpsLimit) -> Vec<OpsLimit> {
+        limit_options(&Self::PRESETS, current)
+    }
+}
+
+impl std::fmt::Display for OpsLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            None => f.write_str("Unlimited ops"),
+            Some(rate) => write!(f, "{rate} ops/s"),
+        }
+    }
+}
+
+/// The presets, plus `current` when it is not one of them.
+fn limit_options<T: Copy + Ord>(presets: &[T], current: T) -> Vec<T> {
+    let mut options = presets.to_vec();
+    if options.contains(&current).not() {
+        options.push(current);
+        options.sort();
+    }
+
+    options
+}
+
 #[derive(Debug, Clone)]
 enum RutabagaState {
     SelectFolder,
@@ -54,16 +168,149 @@ enum RutabagaState {
 
 #[derive(Debug, Clone, Default)]
 struct Progress {
-    renamed: usize,
-    cleared: usize,
-    total: usize,
+    labels: Vec<String>,
+    snapshot: Snapshot,
+}
+
+/// The findings of the discovery scan and the widgets showing them.
+#[derive(Debug, Default)]
+struct Discovery {
+    rows: Vec<FindingRow>,
+    /// Where the findings were exported to, or why they could not be.
+    note: Option<Result<String, String>>,
+    /// Collects the findings of the scan in progress.
+    scanning: Option<Arc<Findings>>,
+    sort: FindingColumn,
+    descending: bool,
+    scan_button_state: button::State,
+    csv_button_state: button::State,
+    json_button_state: button::State,
+    clean_button_state: button::State,
+    header_states: [button::State; 3],
+    scroll_state: scrollable::State,
+}
+
+#[derive(Debug, Clone)]
+struct FindingRow {
+    finding: Finding,
+    selected: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FindingColumn {
+    Path,
+    #[default]
+    Matches,
+    Categories,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl FindingColumn {
+    const ALL: [FindingColumn; 3] = [
+        FindingColumn::Path,
+        FindingColumn::Matches,
+        FindingColumn::Categories,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            FindingColumn::Path => "Path",
+            FindingColumn::Matches => "Matches",
+            FindingColumn::Categories => "Categories",
+        }
+    }
+
+    fn portion(&self) -> u16 {
+        match self {
+            FindingColumn::Path => 3,
+            FindingColumn::Matches => 1,
+            FindingColumn::Categories => 2,
+        }
+    }
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+impl Discovery {
+    fn show(&mut self, findings: Vec<Finding>) {
+        self.rows = findings
+            .into_iter()
+            .map(|finding| FindingRow {
+                finding,
+                selected: false,
+            })
+            .collect();
+        self.note = None;
+        self.sort_rows();
+    }
+
+    fn clear(&mut self) {
+        self.rows.clear();
+        self.note = None;
+    }
+
+    /// Sorts by `column`, in the other direction if it already was. Matches are
+    /// sorted from the most, the other columns alphabetically.
+    fn sort_by(&mut self, column: FindingColumn) {
+        self.descending = match column == self.sort {
+            true => self.descending.not(),
+            false => column == FindingColumn::Matches,
+        };
+        self.sort = column;
+        self.sort_rows();
+    }
+
+    fn sort_rows(&mut self) {
+        let (column, descending) = (self.sort, self.descending);
+
+        self.rows.sort_by(|a, b| {
+            let (a, b) = (&a.finding, &b.finding);
+            let ordering = match column {
+                FindingColumn::Path => a.path.cmp(&b.path),
+                FindingColumn::Matches => a.matches.cmp(&b.matches),
+                FindingColumn::Categories => a.categories.cmp(&b.categories),
+            };
+
+            match descending {
+                true => ordering.reverse(),
+                false => ordering,
+            }
+            .then_with(|| a.path.cmp(&b.path))
+        });
+    }
+
+    fn selected(&self) -> Vec<PathBuf> {
+        self.rows
+            .iter()
+            .filter(|row| row.selected)
+            .map(|row| row.finding.path.clone())
+            .collect()
+    }
 }
 
 impl RutabagaApplication {
-    pub fn start() -> iced::Result {
-        let settings: Settings<()> = Settings {
+    pub fn start(args: Args) -> iced::Result {
+        // Leaves room for the rename preview and the findings of the discovery scan.
+        let size = match args.rename_pattern().is_some() || args.discover {
+            false => (560, 370),
+            true => (560, 560),
+        };
+        let settings: Settings<Args> = Settings {
+            flags: args,
             window: Window {
-                size: (560, 210),
+                size,
                 resizable: false,
                 decorations: true,
                 // icon: Some(application_icon()),
@@ -112,34 +359,116 @@ impl RutabagaApplication {
                 self.start_button_state.enabled = true;
             }
         }
+
+        self.preview_button_state.enabled = self.start_button_state.enabled;
     }
 
     fn clear_progress(&mut self) {
-        self.progress.total = 0;
-        self.progress.renamed = 0;
-        self.progress.cleared = 0;
+        self.progress.snapshot = Snapshot {
+            total: Some(0),
+            ..Default::default()
+        };
+    }
+
+    fn pipeline(&self) -> Pipeline {
+        if let Some(pipeline) = self.args.pipeline() {
+            return pipeline;
+        }
+
+        match self.scrub && self.operation != Operation::RenameClearAndDelete {
+            true => self
+                .operation
+                .pipeline(self.args.rename())
+                .then(self.args.strip_metadata()),
+            false => self.operation.pipeline(self.args.rename()),
+        }
+    }
+
+    /// A job running `pipeline` on the files of the selected folder.
+    fn job(&self, pipeline: Pipeline) -> ClearProcess {
+        let process = ClearProcess::new(self.path_folder.clone(), pipeline)
+            .with_concurrency(self.args.jobs.into())
+            .with_io(self.args.io())
+            .with_limits(self.limits.clone())
+            .with_busy_check(self.args.busy_check())
+            .with_side_files(self.args.side_files());
+
+        match self.args.source(&self.path_folder) {
+            Some(source) => process.with_source(source),
+            None => process,
+        }
+    }
+
+    fn process(&self) -> ClearProcess {
+        let mut process = self.job(self.pipeline());
+
+        if let Some(scan) = &self.args.save_scan {
+            process = process.with_saved_scan(scan.clone());
+        }
+        if self.args.flatten {
+            process = process.with_empty_directories_removed();
+        }
+        if let Some(renamer) = self.args.directory_renamer() {
+            process = process.with_directory_renaming(renamer);
+        }
+        if let Some(report) = &self.args.report {
+            process = process.with_report(report.clone());
+        }
+
+        process
+    }
+
+    fn start_process(&mut self, process: ClearProcess) {
+        self.progress.labels = process.pipeline().labels();
+        self.progress.snapshot = Snapshot {
+            completed: vec![0; process.pipeline().len()],
+            ..Default::default()
+        };
+
+        self.process = Some(process)
     }
 }
 
 impl Application for RutabagaApplication {
     type Executor = iced::executor::Default;
     type Message = Message;
-    type Flags = ();
-
-    fn new(_flags: Self::Flags) -> (Self, Command<Self::Message>) {
-        (
-            Self {
-                path_folder: Default::default(),
-                path_folder_button_state: Default::default(),
-                path_folder_input_state: Default::default(),
-                start_button_state: Default::default(),
-                stop_button_state: Default::default(),
-                current_state: RutabagaState::SelectFolder,
-                progress: Default::default(),
-                process: None,
+    type Flags = Args;
+
+    fn new(flags: Self::Flags) -> (Self, Command<Self::Message>) {
+        let limits = flags.limits();
+        let rate_limits = RateLimit::options(RateLimit(limits.bytes_per_second()));
+        let ops_limits = OpsLimit::options(OpsLimit(limits.ops_per_second()));
+
+        let mut application = Self {
+            path_folder: Default::default(),
+            path_folder_button_state: Default::default(),
+            path_folder_input_state: Default::default(),
+            operation: Default::default(),
+            operation_state: Default::default(),
+            scrub: false,
+            args: flags,
+            limits: Arc::new(limits),
+            rate_limits,
+            rate_limit_state: Default::default(),
+            ops_limits,
+            ops_limit_state: Default::default(),
+            preview: None,
+            preview_button_state: Default::default(),
+            preview_scroll_state: Default::default(),
+            discovery: Discovery {
+                descending: true,
+                ..Default::default()
             },
-            Command::perform(async {}, Message::Clear),
-        )
+            start_button_state: Default::default(),
+            stop_button_state: Default::default(),
+            current_state: RutabagaState::SelectFolder,
+            progress: Default::default(),
+            process: None,
+        };
+        application.progress.labels = application.pipeline().labels();
+        application.clear_progress();
+
+        (application, Command::perform(async {}, Message::Clear))
     }
 
     fn title(&self) -> String {
@@ -148,9 +477,15 @@ impl Application for RutabagaApplication {
 
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
         match message {
-            Message::PathInputChanged(val) => self.path_folder = PathBuf::from(val),
+            Message::PathInputChanged(val) => {
+                self.path_folder = PathBuf::from(val);
+                self.preview = None;
+                self.discovery.clear();
+            }
             Message::Clear(_) => {
                 self.path_folder = Default::default();
+                self.preview = None;
+                self.discovery.clear();
                 self.current_state = RutabagaState::SelectFolder;
                 self.process = None;
                 self.change_enabled();
@@ -161,14 +496,92 @@ impl Application for RutabagaApplication {
             Message::SelectedFolder(path) => {
                 match path {
                     None => {}
-                    Some(path) => self.path_folder = path,
+                    Some(path) => {
+                        self.path_folder = path;
+                        self.preview = None;
+                        self.discovery.clear();
+                    }
                 }
                 self.change_enabled();
             }
-            Message::ProcessStart => {
-                let process = ClearProcess::new(self.path_folder.clone());
-                self.process = Some(process)
+            Message::OperationSelected(operation) => {
+                if self.process.is_none() {
+                    self.operation = operation;
+                    self.progress.labels = self.pipeline().labels();
+                }
+            }
+            Message::ScrubToggled(scrub) => {
+                if self.process.is_none() {
+                    self.scrub = scrub;
+                    self.progress.labels = self.pipeline().labels();
+                }
+            }
+            Message::RateLimitSelected(RateLimit(rate)) => {
+                // Shared with the running job, if any, which picks the new limit up right away.
+                self.limits.set_bytes_per_second(rate)
+            }
+            Message::OpsLimitSelected(OpsLimit(rate)) => self.limits.set_ops_per_second(rate),
+            Message::IdleToggled(when_idle) => self.limits.set_when_idle(when_idle),
+            Message::PreviewRename => {
+                if let Some(pattern) = self.args.rename_pattern() {
+                    let source = self.args.source(&self.path_folder).unwrap_or_else(|| {
+                        Arc::new(DirectorySource::new(self.path_folder.clone()))
+                    });
+
+                    return Command::perform(
+                        preview_rename(pattern, source),
+                        Message::RenamePreviewed,
+                    );
+                }
+            }
+            Message::RenamePreviewed(preview) => self.preview = Some(preview),
+            Message::Discover => {
+                let findings = Arc::new(Findings::default());
+                let pipeline =
+                    Pipeline::new().then(Discover::new(self.args.redact(), findings.clone()));
+
+                self.discovery.clear();
+                self.discovery.scanning = Some(findings);
+                self.start_process(self.job(pipeline));
+            }
+            Message::SortFindings(column) => self.discovery.sort_by(column),
+            Message::FindingToggled(i, selected) => {
+                if let Some(row) = self.discovery.rows.get_mut(i) {
+                    row.selected = selected;
+                }
             }
+            Message::AllFindingsToggled(selected) => {
+                for row in &mut self.discovery.rows {
+                    row.selected = selected;
+                }
+            }
+            Message::ExportFindings(format) => {
+                let findings = self
+                    .discovery
+                    .rows
+                    .iter()
+                    .map(|row| row.finding.clone())
+                    .collect();
+
+                return Command::perform(
+                    export_findings(findings, format),
+                    Message::FindingsExported,
+                );
+            }
+            Message::FindingsExported(result) => match result {
+                Ok(None) => {}
+                Ok(Some(path)) => {
+                    self.discovery.note = Some(Ok(format!("Exported to {}", path.display())))
+                }
+                Err(err) => self.discovery.note = Some(Err(err)),
+            },
+            Message::CleanSelected => {
+                let selection = SelectionSource::new(self.discovery.selected());
+                let process = self.process().with_source(Arc::new(selection));
+
+                self.start_process(process);
+            }
+            Message::ProcessStart => self.start_process(self.process()),
             Message::ProcessCancel => {
                 if let Some(process) = &self.process {
                     process.cancel()
@@ -178,27 +591,33 @@ impl Application for RutabagaApplication {
                 return Command::perform(async {}, Message::Clear);
             }
             Message::Process(progress) => match progress {
-                cleaner::Progress::Started { total } => {
-                    self.progress.total = total;
+                cleaner::Progress::Started => {
                     self.current_state = RutabagaState::Processed;
                     self.change_enabled();
                 }
-                cleaner::Progress::Advanced { renamed, cleared } => {
-                    self.progress.renamed = renamed;
-                    self.progress.cleared = cleared;
-                }
+                cleaner::Progress::Advanced(snapshot) => self.progress.snapshot = snapshot,
                 cleaner::Progress::Finished => {
+                    let snapshot = &mut self.progress.snapshot;
+                    snapshot.total = Some(snapshot.discovered);
                     self.process = None;
+                    // The names it shows are gone.
+                    self.preview = None;
+                    match self.discovery.scanning.take() {
+                        Some(findings) => self.discovery.show(findings.take()),
+                        None => self.discovery.clear(),
+                    }
                     self.current_state = RutabagaState::Finished;
                     self.change_enabled();
                 }
                 cleaner::Progress::Errored => {
                     self.process = None;
+                    self.discovery.scanning = None;
                     self.current_state = RutabagaState::Errored;
                     self.change_enabled();
                 }
                 cleaner::Progress::Canceled => {
                     self.process = None;
+                    self.discovery.scanning = None;
                     self.current_state = RutabagaState::Canceled;
                     self.change_enabled();
                     self.clear_progress();
@@ -210,7 +629,17 @@ impl Application for RutabagaApplication {
     }
 
     fn view(&mut self) -> Element<'_, Self::Message> {
-        Column::new()
+        let custom_actions = match self.args.actions.is_empty() {
+            true => None,
+            false => Some(self.args.describe_actions()),
+        };
+        let source = self
+            .args
+            .source(&self.path_folder)
+            .map(|source| source.to_string());
+
+        let enabled = self.start_button_state.enabled;
+        let mut column = Column::new()
             .spacing(16)
             .width(Length::Fill)
             .height(Length::Fill)
@@ -220,7 +649,41 @@ impl Application for RutabagaApplication {
                 &mut self.path_folder_button_state,
                 &mut self.path_folder_input_state,
             ))
-            .push(Row::new().height(Length::Fill))
+            .push(operation_select(
+                self.operation,
+                &mut self.operation_state,
+                self.scrub,
+                custom_actions,
+            ))
+            .push(limits_select(
+                &self.limits,
+                &self.rate_limits,
+                &mut self.rate_limit_state,
+                &self.ops_limits,
+                &mut self.ops_limit_state,
+            ))
+            .push(source_description(source));
+
+        if self.args.rename_pattern().is_some() {
+            column = column.push(rename_preview(
+                &self.preview,
+                &self.path_folder,
+                &mut self.preview_button_state,
+                &mut self.preview_scroll_state,
+            ));
+        }
+        if self.args.discover {
+            column = column.push(discovery_panel(
+                &mut self.discovery,
+                &self.path_folder,
+                enabled,
+            ));
+        }
+        if self.args.rename_pattern().is_none() && self.args.discover.not() {
+            column = column.push(Row::new().height(Length::Fill));
+        }
+
+        column
             .push(
                 Row::new()
                     .spacing(16)
@@ -232,11 +695,12 @@ impl Application for RutabagaApplication {
                     )),
             )
             .push(line())
-            .push(progress(
-                self.progress.renamed,
-                self.progress.cleared,
-                self.progress.total,
+            .push(progress_bar(
+                &self.progress.snapshot,
+                matches!(self.current_state, RutabagaState::Finished),
             ))
+            .push(progress(&self.progress))
+            .push(current_file(&self.progress.snapshot))
             .into()
     }
 
@@ -292,7 +756,7 @@ fn folder_path<'a>(
         .align_items(Alignment::Center)
 }
 
-fn state_indicator<'a>(state: &RutabagaState) -> iced_native::widget::text::Text<Renderer> {
+fn state_indicator(state: &RutabagaState) -> iced_native::widget::text::Text<Renderer> {
     let (text, color) = match state {
         RutabagaState::SelectFolder => ("Please select a folder", Color::from_rgb8(38, 38, 38)),
         RutabagaState::Processed => ("In process...", Color::from_rgb8(229, 178, 72)),
@@ -334,24 +798,538 @@ fn start_stop_button<'a>(
         )
 }
 
-fn progress<'a>(renamed: usize, cleared: usize, total: usize) -> Element<'a, Message> {
+fn operation_select(
+    operation: Operation,
+    state: &mut pick_list::State<Operation>,
+    scrub: bool,
+    custom_actions: Option<String>,
+) -> Row<'_, Message> {
+    let row = Row::new().spacing(16).align_items(Alignment::Center);
+
+    match custom_actions {
+        None => row
+            .push(
+                Text::new("Operation")
+                    .vertical_alignment(Vertical::Center)
+                    .width(Length::Fill),
+            )
+            .push(Checkbox::new(scrub, "Scrub metadata", Message::ScrubToggled).spacing(8))
+            .push(
+                PickList::new(
+                    state,
+                    &Operation::ALL[..],
+                    Some(operation),
+                    Message::OperationSelected,
+                )
+                .padding(Padding::from([4, 8])),
+            ),
+        Some(actions) => row
+            .push(Text::new("Actions").vertical_alignment(Vertical::Center))
+            .push(
+                Text::new(actions)
+                    .horizontal_alignment(Horizontal::Right)
+                    .vertical_alignment(Vertical::Center)
+                    .width(Length::Fill),
+            ),
+    }
+}
+
+/// Controls of the I/O limits, which also apply to a job already running.
+fn limits_select<'a>(
+    limits: &Limits,
+    rate_options: &'a [RateLimit],
+    rate_state: &'a mut pick_list::State<RateLimit>,
+    ops_options: &'a [OpsLimit],
+    ops_state: &'a mut pick_list::State<OpsLimit>,
+) -> Row<'a, Message> {
+    let rate_limit = RateLimit(limits.bytes_per_second());
+    let ops_limit = OpsLimit(limits.ops_per_second());
+
     Row::new()
-        .spacing(8)
+        .spacing(16)
+        .align_items(Alignment::Center)
         .push(
-            Text::new(format!("Renamed {renamed}/{total}"))
-                .horizontal_alignment(Horizontal::Left)
+            Text::new("I/O limit")
                 .vertical_alignment(Vertical::Center)
                 .width(Length::Fill),
         )
+        .push(Checkbox::new(limits.when_idle(), "Only when idle", Message::IdleToggled).spacing(8))
+        .push(
+            PickList::new(
+                rate_state,
+                rate_options,
+                Some(rate_limit),
+                Message::RateLimitSelected,
+            )
+            .padding(Padding::from([4, 8])),
+        )
         .push(
-            Text::new(format!("Cleared {cleared}/{total}"))
+            PickList::new(
+                ops_state,
+                ops_options,
+                Some(ops_limit),
+                Message::OpsLimitSelected,
+            )
+            .padding(Padding::from([4, 8])),
+        )
+}
+
+fn source_description<'a>(source: Option<String>) -> Row<'a, Message> {
+    let row = Row::new().spacing(16).align_items(Alignment::Center);
+
+    match source {
+        None => row,
+        Some(source) => row
+            .push(Text::new("Source").vertical_alignment(Vertical::Center))
+            .push(
+                Text::new(source)
+                    .horizontal_alignment(Horizontal::Right)
+                    .vertical_alignment(Vertical::Center)
+                    .width(Length::Fill),
+            ),
+    }
+}
+
+/// Two-column list of the current and new names of the files, conflicts in red.
+fn rename_preview<'a>(
+    preview: &Option<Result<Vec<PreviewRow>, String>>,
+    root: &Path,
+    button_state: &'a mut ButtonState,
+    scroll_state: &'a mut scrollable::State,
+) -> Element<'a, Message> {
+    let red = Color::from_rgb8(227, 72, 72);
+    let relative = |path: &Path| {
+        let path = path.strip_prefix(root).unwrap_or(path);
+        path.to_string_lossy().into_owned()
+    };
+
+    let (summary, color) = match preview {
+        None => (String::new(), Color::from_rgb8(38, 38, 38)),
+        Some(Err(err)) => (err.clone(), red),
+        Some(Ok(rows)) => {
+            let conflicts = rows.iter().filter(|row| row.conflict.is_some()).count();
+            let summary = format!("{} files, {conflicts} conflicts", rows.len());
+            match conflicts {
+                0 => (summary, Color::from_rgb8(93, 202, 107)),
+                _ => (summary, red),
+            }
+        }
+    };
+
+    let header = Row::new()
+        .spacing(16)
+        .align_items(Alignment::Center)
+        .push(Text::new("Rename").vertical_alignment(Vertical::Center))
+        .push(
+            Text::new(summary)
                 .horizontal_alignment(Horizontal::Right)
                 .vertical_alignment(Vertical::Center)
-                .width(Length::Fill),
+                .width(Length::Fill)
+                .color(color),
         )
+        .push(
+            button(
+                &mut button_state.state,
+                "Preview",
+                Message::PreviewRename,
+                button_state.enabled,
+            )
+            .style(SecondaryButtonStyle),
+        );
+
+    let rows = match preview {
+        Some(Ok(rows)) => rows.as_slice(),
+        _ => &[],
+    };
+    let mut list = rows.iter().take(PREVIEW_ROWS).fold(
+        Scrollable::new(scroll_state)
+            .spacing(2)
+            .height(Length::Fill),
+        |list, row| {
+            let new_name = match row.conflict {
+                None => Text::new(relative(&row.to)),
+                Some(conflict) => {
+                    Text::new(format!("{} · {conflict}", relative(&row.to))).color(red)
+                }
+            };
+
+            list.push(
+                Row::new()
+                    .spacing(16)
+                    .push(
+                        Text::new(relative(&row.from))
+                            .size(14)
+                            .width(Length::FillPortion(1)),
+                    )
+                    .push(new_name.size(14).width(Length::FillPortion(1))),
+            )
+        },
+    );
+    if rows.len() > PREVIEW_ROWS {
+        list = list.push(Text::new(format!("and {} more", rows.len() - PREVIEW_ROWS)).size(14));
+    }
+
+    Column::new()
+        .spacing(8)
+        .height(Length::Fill)
+        .push(header)
+        .push(list)
         .into()
 }
 
+async fn preview_rename(
+    pattern: pattern::RenamePattern,
+    source: Arc<dyn FileSource>,
+) -> Result<Vec<PreviewRow>, String> {
+    let preview = tokio::task::spawn_blocking(move || pattern::preview(&pattern, source.as_ref()));
+
+    match preview.await {
+        Ok(preview) => preview.map_err(|err| format!("{err:#}")),
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
+
+/// Sortable table of the files the discovery scan found sensitive data in, with
+/// checkboxes picking the ones to clean.
+fn discovery_panel<'a>(
+    discovery: &'a mut Discovery,
+    root: &Path,
+    enabled: bool,
+) -> Element<'a, Message> {
+    let Discovery {
+        rows,
+        note,
+        sort,
+        descending,
+        scan_button_state,
+        csv_button_state,
+        json_button_state,
+        clean_button_state,
+        header_states,
+        scroll_state,
+        ..
+    } = discovery;
+    let relative = |path: &Path| {
+        let path = path.strip_prefix(root).unwrap_or(path);
+        path.to_string_lossy().into_owned()
+    };
+
+    let selected = rows.iter().filter(|row| row.selected).count();
+    let (summary, color) = match note {
+        Some(Ok(note)) => (note.clone(), Color::from_rgb8(93, 202, 107)),
+        Some(Err(err)) => (err.clone(), Color::from_rgb8(227, 72, 72)),
+        None if rows.is_empty() => (String::new(), Color::from_rgb8(38, 38, 38)),
+        None => {
+            let matches = rows.iter().map(|row| row.finding.matches).sum::<usize>();
+            let summary = format!("{} files, {matches} matches", rows.len());
+            (summary, Color::from_rgb8(38, 38, 38))
+        }
+    };
+
+    let header = Row::new()
+        .spacing(16)
+        .align_items(Alignment::Center)
+        .push(Text::new("Findings").vertical_alignment(Vertical::Center))
+        .push(
+            Text::new(summary)
+                .horizontal_alignment(Horizontal::Right)
+                .vertical_alignment(Vertical::Center)
+                .width(Length::Fill)
+                .color(color),
+        )
+        .push(
+            button(scan_button_state, "Scan", Message::Discover, enabled)
+                .style(SecondaryButtonStyle),
+        );
+
+    let all = rows.is_empty().not() && selected == rows.len();
+    let columns = FindingColumn::ALL
+        .into_iter()
+        .zip(header_states.iter_mut())
+        .fold(
+            Row::new().spacing(8).align_items(Alignment::Center),
+            |columns, (column, state)| {
+                let arrow = match (column == *sort, *descending) {
+                    (false, _) => "",
+                    (true, true) => " ↓",
+                    (true, false) => " ↑",
+                };
+                let title = Button::new(
+                    state,
+                    Text::new(format!("{}{arrow}", column.label())).size(14),
+                )
+                .padding(0)
+                .style(HeaderButtonStyle)
+                .on_press(Message::SortFindings(column));
+
+                match column {
+                    FindingColumn::Path => columns.push(
+                        Row::new()
+                            .spacing(8)
+                            .align_items(Alignment::Center)
+                            .width(Length::FillPortion(column.portion()))
+                            .push(
+                                Checkbox::new(all, "", Message::AllFindingsToggled)
+                                    .size(14)
+                                    .spacing(0),
+                            )
+                            .push(title),
+                    ),
+                    _ => columns.push(title.width(Length::FillPortion(column.portion()))),
+                }
+            },
+        );
+
+    let mut list = rows.iter().enumerate().take(FINDING_ROWS).fold(
+        Scrollable::new(scroll_state)
+            .spacing(2)
+            .height(Length::Fill),
+        |list, (i, row)| {
+            list.push(
+                Row::new()
+                    .spacing(8)
+                    .push(
+                        Checkbox::new(row.selected, relative(&row.finding.path), move |selected| {
+                            Message::FindingToggled(i, selected)
+                        })
+                        .size(14)
+                        .text_size(14)
+                        .spacing(8)
+                        .width(Length::FillPortion(FindingColumn::Path.portion())),
+                    )
+                    .push(
+                        Text::new(row.finding.matches.to_string())
+                            .size(14)
+                            .width(Length::FillPortion(FindingColumn::Matches.portion())),
+                    )
+                    .push(
+                        Text::new(row.finding.categories.join(", "))
+                            .size(14)
+                            .width(Length::FillPortion(FindingColumn::Categories.portion())),
+                    ),
+            )
+        },
+    );
+    if rows.len() > FINDING_ROWS {
+        list = list.push(Text::new(format!("and {} more", rows.len() - FINDING_ROWS)).size(14));
+    }
+
+    let exportable = enabled && rows.is_empty().not();
+    let footer = Row::new()
+        .spacing(8)
+        .align_items(Alignment::Center)
+        .push(Space::with_width(Length::Fill))
+        .push(
+            button(
+                csv_button_state,
+                "Export CSV",
+                Message::ExportFindings(ExportFormat::Csv),
+                exportable,
+            )
+            .style(SecondaryButtonStyle),
+        )
+        .push(
+            button(
+                json_button_state,
+                "Export JSON",
+                Message::ExportFindings(ExportFormat::Json),
+                exportable,
+            )
+            .style(SecondaryButtonStyle),
+        )
+        .push(
+            button(
+                clean_button_state,
+                "Clean selected",
+                Message::CleanSelected,
+                enabled && selected > 0,
+            )
+            .style(PrimaryButtonStyle),
+        );
+
+    Column::new()
+        .spacing(8)
+        .height(Length::Fill)
+        .push(header)
+        .push(columns)
+        .push(list)
+        .push(footer)
+        .into()
+}
+
+/// Asks where to save `findings`, then writes them there. `None` when no file was
+/// picked.
+async fn export_findings(
+    findings: Vec<Finding>,
+    format: ExportFormat,
+) -> Result<Option<PathBuf>, String> {
+    let extension = format.extension();
+    let file = rfd::AsyncFileDialog::new()
+        .set_title("Export findings")
+        .add_filter(&extension.to_uppercase(), &[extension])
+        .set_file_name(&format!("findings.{extension}"))
+        .save_file()
+        .await;
+    let path = match file {
+        Some(file) => file.path().to_path_buf(),
+        None => return Ok(None),
+    };
+
+    let export = tokio::task::spawn_blocking(move || {
+        match format {
+            ExportFormat::Csv => discover::export_csv(&findings, &path),
+            ExportFormat::Json => discover::export_json(&findings, &path),
+        }
+        .map(|_| Some(path))
+    });
+
+    match export.await {
+        Ok(export) => export.map_err(|err| format!("{err:#}")),
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
+
+fn progress<'a>(progress: &Progress) -> Element<'a, Message> {
+    let snapshot = &progress.snapshot;
+    let total = match snapshot.total {
+        Some(total) => total.to_string(),
+        None => format!("{}…", snapshot.discovered),
+    };
+    let mut counts = progress
+        .labels
+        .iter()
+        .enumerate()
+        .map(|(i, label)| (label.as_str(), snapshot.completed.get(i).copied()))
+        .collect::<Vec<_>>();
+    if snapshot.skipped > 0 {
+        counts.push(("Skipped", Some(snapshot.skipped)));
+    }
+    let last = counts.len().saturating_sub(1);
+
+    counts
+        .into_iter()
+        .enumerate()
+        .fold(Row::new().spacing(8), |row, (i, (label, count))| {
+            let count = count.unwrap_or_default();
+            let alignment = match i {
+                0 => Horizontal::Left,
+                i if i == last => Horizontal::Right,
+                _ => Horizontal::Center,
+            };
+
+            row.push(
+                Text::new(format!("{label} {count}/{total}"))
+                    .horizontal_alignment(alignment)
+                    .vertical_alignment(Vertical::Center)
+                    .width(Length::Fill),
+            )
+        })
+        .into()
+}
+
+fn progress_bar<'a>(snapshot: &Snapshot, finished: bool) -> Element<'a, Message> {
+    struct ProgressBar {
+        ratio: f32,
+    }
+
+    impl canvas::Program<Message> for ProgressBar {
+        fn draw(&self, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+            let mut frame = Frame::new(bounds.size());
+
+            frame.fill_rectangle(
+                Point::ORIGIN,
+                bounds.size(),
+                Color::from_rgb8(222, 222, 222),
+            );
+            frame.fill_rectangle(
+                Point::ORIGIN,
+                Size::new(bounds.width * self.ratio, bounds.height),
+                Color::from_rgb8(93, 202, 107),
+            );
+
+            vec![frame.into_geometry()]
+        }
+    }
+
+    let ratio = match finished {
+        true => 1.0,
+        false => snapshot.ratio(),
+    };
+
+    Canvas::new(ProgressBar { ratio })
+        .width(Length::Fill)
+        .height(Length::Units(6))
+        .into()
+}
+
+fn current_file<'a>(snapshot: &Snapshot) -> Row<'a, Message> {
+    let name = snapshot
+        .current
+        .as_ref()
+        .and_then(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let total = snapshot.total_bytes.unwrap_or(snapshot.discovered_bytes);
+    let mut details = format!(
+        "{} / {}",
+        format_bytes(snapshot.bytes as f64),
+        format_bytes(total as f64)
+    );
+    if snapshot.current.is_some() {
+        details += &format!(" · {}/s", format_bytes(snapshot.throughput));
+        if let Some(eta) = snapshot.eta() {
+            details += &format!(" · {} left", format_duration(eta));
+        }
+    }
+    if let Some(ratio) = snapshot.compression_ratio() {
+        details += &format!(
+            " · {ratio:.1}× smaller, {} saved",
+            format_bytes(snapshot.bytes_saved() as f64)
+        );
+    }
+
+    Row::new()
+        .spacing(8)
+        .push(
+            Text::new(name)
+                .horizontal_alignment(Horizontal::Left)
+                .vertical_alignment(Vertical::Center)
+                .width(Length::Fill),
+        )
+        .push(
+            Text::new(details)
+                .horizontal_alignment(Horizontal::Right)
+                .vertical_alignment(Vertical::Center),
+        )
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{value:.0} {}", UNITS[unit]),
+        _ => format!("{value:.1} {}", UNITS[unit]),
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    match seconds {
+        0..=59 => format!("{seconds}s"),
+        60..=3599 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
 fn line<'a>() -> Element<'a, Message> {
     struct LineStyle;
     impl container::StyleSheet for LineStyle {
@@ -379,5 +1357,5 @@ async fn select_folder() -> Option<PathBuf> {
         .pick_folder()
         .await;
 
-    path.map_or(None, |f| Some(f.path().to_path_buf()))
+    path.map(|f| f.path().to_path_buf())
 }
@@ -95,3 +95,25 @@ impl button::StyleSheet for SecondaryButtonStyle {
         }
     }
 }
+
+/// Column headers that sort a table when clicked.
+pub struct HeaderButtonStyle;
+impl button::StyleSheet for HeaderButtonStyle {
+    fn active(&self) -> button::Style {
+        button::Style {
+            shadow_offset: Default::default(),
+            border_color: Color::TRANSPARENT,
+            border_width: 0.,
+            border_radius: 0.,
+            text_color: color!(0x6B6B6B),
+            background: None,
+        }
+    }
+
+    fn hovered(&self) -> button::Style {
+        button::Style {
+            text_color: color!(0x262626),
+            ..self.active()
+        }
+    }
+}
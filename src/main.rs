@@ -1,10 +1,51 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use crate::cli::{Args, Key};
+use crate::gui::cleaner::pseudonym;
 use crate::gui::RutabagaApplication;
+use anyhow::Context;
+use rfd::{MessageDialog, MessageLevel};
+use std::path::Path;
 
+mod cli;
 mod gui;
 
 #[tokio::main]
 async fn main() -> iced::Result {
-    RutabagaApplication::start()
+    let args = Args::parse_checked();
+
+    if let (Some(output), Some(Key(key))) = (&args.reveal_mapping, &args.pseudonym_key) {
+        // Shown in a dialog, since release builds on Windows have no console.
+        let (level, message) = match reveal_mapping(key, &args.mapping(), output) {
+            Ok(count) => (
+                MessageLevel::Info,
+                format!("{count} renames written to {}", output.display()),
+            ),
+            Err(err) => (MessageLevel::Error, format!("{err:#}")),
+        };
+        MessageDialog::new()
+            .set_level(level)
+            .set_title("Rutabaga")
+            .set_description(&message)
+            .show();
+
+        if let MessageLevel::Error = level {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    RutabagaApplication::start(args)
+}
+
+/// Writes the renames recorded in `mapping` to `output` and returns how many there are.
+fn reveal_mapping(key: &[u8], mapping: &Path, output: &Path) -> anyhow::Result<usize> {
+    let renames = pseudonym::reveal(key, mapping)?;
+    let lines: String = renames
+        .iter()
+        .map(|rename| format!("{}\t{}\n", rename.from.display(), rename.to.display()))
+        .collect();
+
+    std::fs::write(output, lines).with_context(|| format!("writing {}", output.display()))?;
+    Ok(renames.len())
 }